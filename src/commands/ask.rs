@@ -0,0 +1,127 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::components::{Logger, Severity};
+use crate::messages::{PromptSource, cooldown_message};
+use serenity::all::{CommandOptionType, CreateCommandOption, ResolvedValue};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+
+pub struct Ask {
+    logger: Logger,
+}
+
+impl Ask {
+    pub fn new() -> Self {
+        Self {
+            logger: Logger::new("Ask"),
+        }
+    }
+}
+
+#[async_trait]
+impl SlashCommand for Ask {
+    fn name(&self) -> &'static str {
+        "ask"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "vraag")
+            .description("Ask me anything")
+            .description_localized(DUTCH, "Vraag me alles")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "prompt",
+                    "What you want to ask me.",
+                )
+                .name_localized(DUTCH, "vraag")
+                .description_localized(DUTCH, "Wat je me wilt vragen.")
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "private",
+                    "Only you can see my answer.",
+                )
+                .name_localized(DUTCH, "privé")
+                .description_localized(DUTCH, "Alleen jij kunt mijn antwoord zien."),
+            )
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        let mut prompt = String::new();
+        let mut private = false;
+
+        for option in cmd.options() {
+            match (option.name, &option.value) {
+                ("prompt", ResolvedValue::String(value)) => prompt = value.to_string(),
+                ("private", ResolvedValue::Boolean(value)) => private = *value,
+                _ => {}
+            }
+        }
+
+        if prompt.trim().is_empty() {
+            return CommandResponse::text("Error: no prompt provided").ephemeral();
+        }
+
+        let allowed = cmd
+            .state
+            .ai_dolly
+            .reply_policy()
+            .allows(
+                cmd.ctx,
+                cmd.command.guild_id,
+                cmd.command.channel_id,
+                &cmd.command.user,
+            )
+            .await;
+
+        if !allowed {
+            return CommandResponse::text("I'm not allowed to talk here").ephemeral();
+        }
+
+        let roles = cmd
+            .command
+            .member
+            .as_ref()
+            .map(|member| member.roles.clone())
+            .unwrap_or_default();
+
+        if let Err(wait_time) = cmd.state.ai_dolly.rate_limiter().check(
+            cmd.ctx.shard_id.0,
+            cmd.command.user.id,
+            cmd.command.channel_id,
+            &roles,
+        ) {
+            cmd.state
+                .metrics
+                .rate_limited
+                .with_label_values(&[&cmd.ctx.shard_id.to_string()])
+                .inc();
+            return CommandResponse::text(cooldown_message(wait_time)).ephemeral();
+        }
+
+        // Generating can easily take longer than the 3 seconds Discord gives us
+        if let Err(why) = cmd.defer(private).await {
+            self.logger.error(
+                format!("Could not defer ask: {}", why).as_str(),
+                Severity::Medium,
+            );
+        }
+
+        let source = PromptSource {
+            author: &cmd.command.user,
+            content: &prompt,
+            channel_id: cmd.command.channel_id,
+            guild_id: cmd.command.guild_id,
+            mentions: &[],
+        };
+
+        let answer = cmd.state.ai_dolly.get_llm_message(cmd.ctx, &source).await;
+
+        let mut response = CommandResponse::text(answer);
+        response.ephemeral = private;
+        response
+    }
+}
@@ -1,51 +1,66 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
 use serenity::all::{CommandOptionType, CreateCommandOption, ResolvedValue};
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
-use std::{env, fs};
+use serenity::model::Permissions;
+use std::fs;
 
 fn write_system_prompt(prompt: String) -> Result<(), std::io::Error> {
     fs::write("system_message.txt", prompt)
 }
 
-pub fn run(options: &[ResolvedOption]) -> String {
-    let allow_changing =
-        env::var("ALLOW_CHANGING_SYSTEM_PROMPT").unwrap_or("false".to_string()) == "true";
+pub struct ChangeSystemPrompt;
 
-    if !allow_changing {
-        return "Not allowed to change the system prompt".to_string();
+#[async_trait]
+impl SlashCommand for ChangeSystemPrompt {
+    fn name(&self) -> &'static str {
+        "change_system_prompt"
     }
 
-    let mut prompt = String::new();
-
-    for option in options {
-        if option.name == "prompt"
-            && let ResolvedValue::String(value) = &option.value
-        {
-            prompt = value.to_string();
-        }
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_GUILD
     }
 
-    if prompt.is_empty() {
-        return "Error: no prompt provided".to_string();
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "systeemprompt_wijzigen")
+            .description("Allows you to change the system prompt")
+            .description_localized(DUTCH, "Hiermee kun je de systeemprompt wijzigen")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "prompt",
+                    "The prompt with how you want it to respond.",
+                )
+                .description_localized(DUTCH, "De prompt met hoe ik moet antwoorden.")
+                .required(true),
+            )
     }
 
-    let result = write_system_prompt(prompt);
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        if !cmd.state.config.get().allow_changing_system_prompt {
+            return CommandResponse::text("Not allowed to change the system prompt");
+        }
 
-    match result {
-        Ok(()) => "Successfully changed prompt".to_string(),
-        Err(_) => "Failed to edit the system prompt".to_string(),
-    }
-}
+        let mut prompt = String::new();
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("change_system_prompt")
-        .description("Allows you to change the system prompt")
-        .add_option(
-            CreateCommandOption::new(
-                CommandOptionType::String,
-                "prompt",
-                "The prompt with how you want it to respond.",
-            )
-            .required(true),
-        )
+        for option in cmd.options() {
+            if option.name == "prompt"
+                && let ResolvedValue::String(value) = &option.value
+            {
+                prompt = value.to_string();
+            }
+        }
+
+        if prompt.is_empty() {
+            return CommandResponse::text("Error: no prompt provided");
+        }
+
+        let result = write_system_prompt(prompt);
+
+        match result {
+            Ok(()) => CommandResponse::text("Successfully changed prompt"),
+            Err(_) => CommandResponse::text("Failed to edit the system prompt"),
+        }
+    }
 }
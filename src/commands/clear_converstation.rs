@@ -1,18 +1,33 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
 use crate::messages::MessageHandler;
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
+use serenity::model::Permissions;
 
-pub fn run(
-    _options: &[ResolvedOption],
-    ai_dolly_handler: &(dyn MessageHandler + Send + Sync),
-) -> String {
-    if ai_dolly_handler.clean_up() {
-        return "Successfully cleared conversation".to_string();
+pub struct ClearConversation;
+
+#[async_trait]
+impl SlashCommand for ClearConversation {
+    fn name(&self) -> &'static str {
+        "clearconversation"
     }
 
-    "Could not clear conversation".to_string()
-}
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_MESSAGES
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("clearconversation").description("Clears AI conversation")
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "gesprek_wissen")
+            .description("Clears AI conversation")
+            .description_localized(DUTCH, "Wist het AI-gesprek")
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        if cmd.state.ai_dolly.clean_up() {
+            return CommandResponse::text("Successfully cleared conversation");
+        }
+
+        CommandResponse::text("Could not clear conversation")
+    }
 }
@@ -0,0 +1,74 @@
+use super::SlashCommand;
+use crate::components::CommandPermissions;
+use serenity::builder::CreateCommand;
+
+/// Holds every slash command so they can be registered and dispatched in one place
+///
+/// # Fields
+///
+/// * `commands` - All the commands that have been added
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn SlashCommand>>,
+}
+
+impl CommandRegistry {
+    /// Constructor
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Adds a command to the registry
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that should be available
+    pub fn add<C: SlashCommand + 'static>(&mut self, command: C) {
+        self.commands.push(Box::new(command));
+    }
+
+    /// Finds a command by its name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command. Like `ping`
+    pub fn get(&self, name: &str) -> Option<&dyn SlashCommand> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .map(|command| command.as_ref())
+    }
+
+    /// All the commands inside of the registry
+    pub fn iter(&self) -> impl Iterator<Item = &dyn SlashCommand> {
+        self.commands.iter().map(|command| command.as_ref())
+    }
+
+    /// Creates the commands that get registered at Discord. Commands with an allow-list are
+    /// registered without their default permissions, so Discord shows them to the allowed
+    /// members and the bot checks the permissions itself
+    ///
+    /// # Arguments
+    ///
+    /// * `command_permissions` - Who can use which slash command
+    /// * `include` - Decides by name which commands are created
+    pub fn create_commands(
+        &self,
+        command_permissions: &CommandPermissions,
+        include: impl Fn(&str) -> bool,
+    ) -> Vec<CreateCommand> {
+        self.iter()
+            .filter(|command| include(command.name()))
+            .map(|command| {
+                let permissions = command.permissions();
+
+                if permissions.is_empty() || command_permissions.has_allow_list(command.name()) {
+                    command.register()
+                } else {
+                    command.register().default_member_permissions(permissions)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,250 @@
+use crate::components::Logger;
+use serde_json::{Map, Value};
+use serenity::builder::CreateCommand;
+use serenity::http::Http;
+use serenity::model::application::Command;
+use serenity::model::id::GuildId;
+use std::fmt;
+
+/// Where slash commands are registered
+///
+/// * `Global` - In every server and in direct messages. Can take a while to show up
+/// * `Guild` - Only in one server, but they show up right away
+#[derive(Debug, Clone, Copy)]
+pub enum CommandScope {
+    Global,
+    Guild(GuildId),
+}
+
+impl fmt::Display for CommandScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Guild(guild_id) => write!(f, "guild {}", guild_id),
+        }
+    }
+}
+
+/// What a sync changed at Discord
+///
+/// # Fields
+///
+/// * `created` - The names of the commands that were new
+/// * `updated` - The names of the commands that had a different description or options
+/// * `deleted` - The names of the commands that are not in the registry anymore
+/// * `unchanged` - The amount of commands that were already up to date
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl fmt::Display for SyncSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |names: &[String]| match names.is_empty() {
+            true => "none".to_string(),
+            false => names.join(", "),
+        };
+
+        write!(
+            f,
+            "created {}, updated {}, deleted {}, {} unchanged",
+            list(&self.created),
+            list(&self.updated),
+            list(&self.deleted),
+            self.unchanged
+        )
+    }
+}
+
+/// Registers slash commands by comparing them with the ones Discord already has, so only the
+/// commands that changed cost a request
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+pub struct CommandSync {
+    logger: Logger,
+}
+
+impl CommandSync {
+    /// Constructor
+    pub fn new() -> Self {
+        Self {
+            logger: Logger::new("CommandSync"),
+        }
+    }
+
+    /// Creates, updates and deletes commands until Discord has exactly the given commands
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - Used for talking to Discord
+    /// * `scope` - If the global or the guild commands are synced
+    /// * `commands` - The commands that should be registered
+    pub async fn sync(
+        &self,
+        http: &Http,
+        scope: CommandScope,
+        commands: Vec<CreateCommand>,
+    ) -> serenity::Result<SyncSummary> {
+        let mut existing = match scope {
+            CommandScope::Global => Command::get_global_commands_with_localizations(http).await?,
+            CommandScope::Guild(guild_id) => guild_id.get_commands_with_localizations(http).await?,
+        };
+
+        let mut summary = SyncSummary::default();
+
+        for command in commands {
+            let wanted = command_shape(serde_json::to_value(&command)?);
+            let name = wanted["name"].as_str().unwrap_or_default().to_string();
+
+            let Some(index) = existing.iter().position(|current| current.name == name) else {
+                self.logger
+                    .debug(format!("Creating the {} command {}", scope, name).as_str());
+                match scope {
+                    CommandScope::Global => {
+                        Command::create_global_command(http, command).await?;
+                    }
+                    CommandScope::Guild(guild_id) => {
+                        guild_id.create_command(http, command).await?;
+                    }
+                }
+                summary.created.push(name);
+                continue;
+            };
+
+            let current = existing.swap_remove(index);
+            if command_shape(serde_json::to_value(&current)?) == wanted {
+                summary.unchanged += 1;
+                continue;
+            }
+
+            self.logger
+                .debug(format!("Updating the {} command {}", scope, name).as_str());
+            match scope {
+                CommandScope::Global => {
+                    Command::edit_global_command(http, current.id, command).await?;
+                }
+                CommandScope::Guild(guild_id) => {
+                    guild_id.edit_command(http, current.id, command).await?;
+                }
+            }
+            summary.updated.push(name);
+        }
+
+        // Whatever is left isn't in the registry anymore
+        for current in existing {
+            self.logger
+                .debug(format!("Deleting the {} command {}", scope, current.name).as_str());
+            match scope {
+                CommandScope::Global => Command::delete_global_command(http, current.id).await?,
+                CommandScope::Guild(guild_id) => guild_id.delete_command(http, current.id).await?,
+            }
+            summary.deleted.push(current.name);
+        }
+
+        Ok(summary)
+    }
+
+    /// Deletes every command in one request
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - Used for talking to Discord
+    /// * `scope` - If the global or the guild commands are deleted
+    pub async fn purge(&self, http: &Http, scope: CommandScope) -> serenity::Result<()> {
+        match scope {
+            CommandScope::Global => Command::set_global_commands(http, Vec::new()).await?,
+            CommandScope::Guild(guild_id) => guild_id.set_commands(http, Vec::new()).await?,
+        };
+
+        self.logger
+            .info(format!("Deleted every {} command", scope).as_str());
+        Ok(())
+    }
+}
+
+/// The parts of a command that are compared. Discord adds ids and versions and leaves out
+/// empty values, so both sides are brought into the same form first
+///
+/// # Arguments
+///
+/// * `command` - A `CreateCommand` or a `Command` as JSON
+fn command_shape(command: Value) -> Value {
+    let mut shape = Map::new();
+
+    shape.insert("name".to_string(), command["name"].clone());
+    shape.insert(
+        "name_localizations".to_string(),
+        or_empty_object(&command["name_localizations"]),
+    );
+    shape.insert(
+        "description".to_string(),
+        command["description"].as_str().unwrap_or_default().into(),
+    );
+    shape.insert(
+        "description_localizations".to_string(),
+        or_empty_object(&command["description_localizations"]),
+    );
+    shape.insert(
+        "default_member_permissions".to_string(),
+        command["default_member_permissions"].clone(),
+    );
+    shape.insert("options".to_string(), options_shape(&command["options"]));
+
+    Value::Object(shape)
+}
+
+/// The options of a command in the form `command_shape` compares
+///
+/// # Arguments
+///
+/// * `options` - The `options` array of a command or an option
+fn options_shape(options: &Value) -> Value {
+    let Some(options) = options.as_array() else {
+        return Value::Array(Vec::new());
+    };
+
+    options
+        .iter()
+        .map(|option| {
+            let mut option = option.as_object().cloned().unwrap_or_default();
+
+            for key in ["name_localizations", "description_localizations"] {
+                let localizations = or_empty_object(option.get(key).unwrap_or(&Value::Null));
+                option.insert(key.to_string(), localizations);
+            }
+
+            // `1` and `1.0` are the same limit
+            for key in ["min_value", "max_value"] {
+                if let Some(number) = option.get(key).and_then(Value::as_f64) {
+                    option.insert(key.to_string(), number.into());
+                }
+            }
+
+            if let Some(Value::Array(choices)) = option.get_mut("choices") {
+                for choice in choices.iter_mut().filter_map(Value::as_object_mut) {
+                    let localizations =
+                        or_empty_object(choice.get("name_localizations").unwrap_or(&Value::Null));
+                    choice.insert("name_localizations".to_string(), localizations);
+                }
+            }
+
+            let nested = options_shape(option.get("options").unwrap_or(&Value::Null));
+            option.insert("options".to_string(), nested);
+
+            Value::Object(option)
+        })
+        .collect()
+}
+
+/// Discord leaves out localizations that have not been set, while the builder sends `{}`
+fn or_empty_object(value: &Value) -> Value {
+    match value {
+        Value::Object(_) => value.clone(),
+        _ => Value::Object(Map::new()),
+    }
+}
@@ -1,8 +1,19 @@
 // Files
+pub mod ask;
 pub mod change_system_prompt;
 pub mod clear_converstation;
+pub mod command_registry;
+pub mod command_sync;
 pub mod ping;
 pub mod quote;
 pub mod rage;
+pub mod respond_mode;
+pub mod slash_command;
+pub mod status;
 pub mod system_prompt;
+pub mod triggers;
+pub mod usage;
 pub mod version;
+pub use command_registry::*;
+pub use command_sync::*;
+pub use slash_command::*;
@@ -1,10 +1,22 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
 
-pub fn run(_options: &[ResolvedOption]) -> String {
-    "Hey, I'm alive!".to_string()
-}
+pub struct Ping;
+
+#[async_trait]
+impl SlashCommand for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Checks if I'm alive lol")
+            .description_localized(DUTCH, "Checkt of ik nog leef lol")
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("ping").description("Checks if I'm alive lol")
+    async fn run(&self, _cmd: &CommandContext<'_>) -> CommandResponse {
+        CommandResponse::text("Hey, I'm alive!")
+    }
 }
@@ -1,8 +1,8 @@
-use reqwest::Client;
-use rust_logger::{Logger, Severity};
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::components::{HttpClient, Logger, Severity};
 use serde::Deserialize;
-use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateEmbed, CreateEmbedFooter};
 
 #[derive(Debug, Deserialize)]
 struct ReceivedQuote {
@@ -10,35 +10,65 @@ struct ReceivedQuote {
     author: String,
 }
 
-async fn get_quote() -> Result<String, reqwest::Error> {
-    let client = Client::builder()
-        .danger_accept_invalid_certs(true) // This disables SSL certificate verification, because it seems to be having issues often
-        .build()?;
+async fn get_quote(http: &HttpClient) -> Result<ReceivedQuote, reqwest::Error> {
+    let url = "https://api.quotable.io/random";
 
-    let resp: ReceivedQuote = client
-        .get("https://api.quotable.io/random")
+    http.client()
+        .get(url)
+        .timeout(http.timeout(url))
         .send()
         .await?
+        .error_for_status()?
         .json()
-        .await?;
+        .await
+}
 
-    let quote = format!("```{} --{}```", resp.content, resp.author);
-    Ok(quote)
+pub struct Quote {
+    logger: Logger,
 }
 
-pub async fn run(logger: Logger, _options: &[ResolvedOption<'_>]) -> String {
-    match get_quote().await {
-        Ok(quote) => quote,
-        Err(e) => {
-            logger.error(
-                format!("Could not get quote: {}", e).as_str(),
-                Severity::High,
-            );
-            "Something went wrong 😭".to_string()
+impl Quote {
+    pub fn new() -> Self {
+        Self {
+            logger: Logger::new("Quote"),
         }
     }
 }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("quote").description("Generates random quote")
+#[async_trait]
+impl SlashCommand for Quote {
+    fn name(&self) -> &'static str {
+        "quote"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "citaat")
+            .description("Generates random quote")
+            .description_localized(DUTCH, "Genereert een willekeurig citaat")
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        if let Err(why) = cmd.defer(false).await {
+            self.logger.error(
+                format!("Could not defer quote: {}", why).as_str(),
+                Severity::Medium,
+            );
+        }
+
+        match get_quote(&cmd.state.http).await {
+            Ok(quote) => CommandResponse::embed(
+                CreateEmbed::new()
+                    .description(quote.content)
+                    .footer(CreateEmbedFooter::new(format!("--{}", quote.author))),
+            ),
+            Err(e) => {
+                self.logger.error(
+                    format!("Could not get quote: {}", e).as_str(),
+                    Severity::High,
+                );
+                CommandResponse::text("Something went wrong 😭")
+            }
+        }
+    }
 }
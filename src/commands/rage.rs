@@ -1,10 +1,23 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
 
-pub fn run(_options: &[ResolvedOption]) -> String {
-    "It's okay to be angry sometimes! Just don't make it 9 to 5 :)".to_string()
-}
+pub struct Rage;
+
+#[async_trait]
+impl SlashCommand for Rage {
+    fn name(&self) -> &'static str {
+        "rage"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "woede")
+            .description("Why u mad :(")
+            .description_localized(DUTCH, "Waarom ben je boos :(")
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("rage").description("Why u mad :(")
+    async fn run(&self, _cmd: &CommandContext<'_>) -> CommandResponse {
+        CommandResponse::text("It's okay to be angry sometimes! Just don't make it 9 to 5 :)")
+    }
 }
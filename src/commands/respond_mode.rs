@@ -0,0 +1,80 @@
+use super::triggers::{get_scope, scope_option};
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::messages::RespondMode;
+use serenity::all::{CommandOptionType, CreateCommandOption, ResolvedValue};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::Permissions;
+
+pub struct RespondModeCommand;
+
+#[async_trait]
+impl SlashCommand for RespondModeCommand {
+    fn name(&self) -> &'static str {
+        "respond_mode"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_GUILD
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "reactiemodus")
+            .description("Changes when I respond to messages")
+            .description_localized(DUTCH, "Verandert wanneer ik op berichten reageer")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "mode", "When I respond.")
+                    .name_localized(DUTCH, "modus")
+                    .description_localized(DUTCH, "Wanneer ik reageer.")
+                    .add_string_choice_localized(
+                        "all messages",
+                        RespondMode::All.as_str(),
+                        [(DUTCH, "alle berichten")],
+                    )
+                    .add_string_choice_localized(
+                        "only when pinged",
+                        RespondMode::Mentions.as_str(),
+                        [(DUTCH, "alleen als ik gepingd word")],
+                    )
+                    .add_string_choice_localized(
+                        "pings and trigger words",
+                        RespondMode::Triggers.as_str(),
+                        [(DUTCH, "pings en triggerwoorden")],
+                    )
+                    .add_string_choice_localized(
+                        "never",
+                        RespondMode::Off.as_str(),
+                        [(DUTCH, "nooit")],
+                    )
+                    .required(true),
+            )
+            .add_option(scope_option())
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        let options = cmd.options();
+
+        let mut mode = None;
+        for option in &options {
+            if option.name == "mode"
+                && let ResolvedValue::String(value) = &option.value
+            {
+                mode = RespondMode::parse(value);
+            }
+        }
+
+        let Some(mode) = mode else {
+            return CommandResponse::text("Error: invalid mode provided").ephemeral();
+        };
+
+        let scope = get_scope(cmd, &options);
+        cmd.state.ai_dolly.response_settings().set_mode(scope, mode);
+
+        CommandResponse::text(format!(
+            "Respond mode for {} is now `{}`",
+            scope.name(),
+            mode.as_str()
+        ))
+    }
+}
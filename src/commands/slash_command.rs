@@ -0,0 +1,225 @@
+use crate::components::SharedState;
+use serenity::all::ResolvedOption;
+use serenity::async_trait;
+use serenity::builder::{
+    CreateCommand, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, EditInteractionResponse,
+};
+use serenity::client::Context;
+use serenity::model::Permissions;
+use serenity::model::application::CommandInteraction;
+use serenity::model::channel::Message;
+use std::sync::Mutex;
+
+/// How far the interaction has been answered
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ResponseState {
+    Pending,
+    Deferred,
+    Responded,
+}
+
+/// Everything a command has access to while it runs
+///
+/// # Fields
+///
+/// * `ctx` - The context from where the interaction is from.
+/// * `command` - The received command interaction
+/// * `state` - The state that is shared between the handler and the commands
+/// * `response_state` - Whether the interaction already got a (deferred) response
+pub struct CommandContext<'a> {
+    pub ctx: &'a Context,
+    pub command: &'a CommandInteraction,
+    pub state: &'a SharedState,
+    response_state: Mutex<ResponseState>,
+}
+
+impl<'a> CommandContext<'a> {
+    /// Constructor
+    pub fn new(ctx: &'a Context, command: &'a CommandInteraction, state: &'a SharedState) -> Self {
+        Self {
+            ctx,
+            command,
+            state,
+            response_state: Mutex::new(ResponseState::Pending),
+        }
+    }
+
+    /// The options the user filled in
+    pub fn options(&self) -> Vec<ResolvedOption<'_>> {
+        self.command.data.options()
+    }
+
+    /// Tells Discord the reply is coming later, so the command can take longer than 3 seconds.
+    /// The reply that is sent afterwards will replace the "is thinking..." message.
+    ///
+    /// # Arguments
+    ///
+    /// * `ephemeral` - Whether only the user of the command can see the reply
+    pub async fn defer(&self, ephemeral: bool) -> serenity::Result<()> {
+        let builder = CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new().ephemeral(ephemeral),
+        );
+
+        self.command
+            .create_response(&self.ctx.http, builder)
+            .await?;
+        self.set_response_state(ResponseState::Deferred);
+        Ok(())
+    }
+
+    /// Sends the reply. If the command deferred it replaces the "is thinking..." message and if
+    /// the command already replied it gets sent as a follow up.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The reply of the command
+    pub async fn respond(&self, response: CommandResponse) -> serenity::Result<()> {
+        let response_state = *self.response_state.lock().unwrap();
+
+        match response_state {
+            ResponseState::Pending => {
+                let builder = CreateInteractionResponse::Message(response.into_message());
+                self.command
+                    .create_response(&self.ctx.http, builder)
+                    .await?;
+            }
+            ResponseState::Deferred => {
+                self.edit_response(response).await?;
+            }
+            ResponseState::Responded => {
+                self.follow_up(response).await?;
+            }
+        }
+
+        self.set_response_state(ResponseState::Responded);
+        Ok(())
+    }
+
+    fn set_response_state(&self, response_state: ResponseState) {
+        *self.response_state.lock().unwrap() = response_state;
+    }
+
+    /// Replaces the content of the original reply. Whether it is ephemeral can't be changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The new reply of the command
+    pub async fn edit_response(&self, response: CommandResponse) -> serenity::Result<Message> {
+        self.command
+            .edit_response(&self.ctx.http, response.into_edit())
+            .await
+    }
+
+    /// Sends an extra message after the original reply
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The extra message
+    pub async fn follow_up(&self, response: CommandResponse) -> serenity::Result<Message> {
+        self.command
+            .create_followup(&self.ctx.http, response.into_follow_up())
+            .await
+    }
+}
+
+/// The reply of a command
+///
+/// # Fields
+///
+/// * `content` - The text of the reply
+/// * `embeds` - The embeds of the reply
+/// * `ephemeral` - Whether only the user of the command can see the reply
+#[derive(Debug, Clone, Default)]
+pub struct CommandResponse {
+    pub content: Option<String>,
+    pub embeds: Vec<CreateEmbed>,
+    pub ephemeral: bool,
+}
+
+impl CommandResponse {
+    /// Creates a reply with only text
+    pub fn text<S: Into<String>>(content: S) -> Self {
+        Self {
+            content: Some(content.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a reply with only an embed
+    pub fn embed(embed: CreateEmbed) -> Self {
+        Self {
+            embeds: vec![embed],
+            ..Default::default()
+        }
+    }
+
+    /// Makes the reply only visible to the user of the command
+    pub fn ephemeral(mut self) -> Self {
+        self.ephemeral = true;
+        self
+    }
+
+    /// Turns the reply into a message Discord understands
+    pub fn into_message(self) -> CreateInteractionResponseMessage {
+        let mut message = CreateInteractionResponseMessage::new()
+            .embeds(self.embeds)
+            .ephemeral(self.ephemeral);
+
+        if let Some(content) = self.content {
+            message = message.content(content);
+        }
+
+        message
+    }
+
+    /// Turns the reply into an edit of the original reply
+    pub fn into_edit(self) -> EditInteractionResponse {
+        let mut edit = EditInteractionResponse::new().embeds(self.embeds);
+
+        if let Some(content) = self.content {
+            edit = edit.content(content);
+        }
+
+        edit
+    }
+
+    /// Turns the reply into a follow up message
+    pub fn into_follow_up(self) -> CreateInteractionResponseFollowup {
+        let mut follow_up = CreateInteractionResponseFollowup::new()
+            .embeds(self.embeds)
+            .ephemeral(self.ephemeral);
+
+        if let Some(content) = self.content {
+            follow_up = follow_up.content(content);
+        }
+
+        follow_up
+    }
+}
+
+/// The locale of the Dutch names and descriptions. English is the default of every command
+pub const DUTCH: &str = "nl";
+
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    /// The name that is used to call the command. Like `ping`
+    fn name(&self) -> &'static str;
+
+    /// The Discord permissions a member needs to use the command
+    fn permissions(&self) -> Permissions {
+        Permissions::empty()
+    }
+
+    /// Creates the command that gets registered at Discord. Discord shows the `DUTCH`
+    /// localizations to users that use Discord in Dutch
+    fn register(&self) -> CreateCommand;
+
+    /// This function will run the command and return the reply. Slow commands should call
+    /// `cmd.defer()` first, the returned reply will then replace the deferred response.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The context, interaction and shared state of the command
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse;
+}
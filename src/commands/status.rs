@@ -0,0 +1,211 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::components::{ShardManagerContainer, redact_secrets};
+use crate::messages::{Provider, ProviderStat};
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateEmbed};
+use std::time::Duration;
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+
+    match (days, hours) {
+        (0, 0) => format!("{}m {}s", minutes, seconds % 60),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// Errors can contain a whole response body with tokens or urls with passwords, while
+/// everyone in the channel can see the status
+fn format_error(error: &str) -> String {
+    // Redacted first, so a secret that is cut off can't be shown partly
+    redact_secrets(error).chars().take(200).collect()
+}
+
+/// The models of the Ollama server, cut off like the errors, because an embed field can only
+/// have 1024 characters
+fn format_models(models: &[String]) -> String {
+    let mut shown = String::new();
+
+    for (index, model) in models.iter().enumerate() {
+        let separator = if shown.is_empty() { "" } else { ", " };
+
+        if shown.len() + separator.len() + model.len() > 200 {
+            let rest = format!("…and {} more", models.len() - index);
+            return match shown.is_empty() {
+                true => rest,
+                false => format!("{} {}", shown, rest),
+            };
+        }
+
+        shown.push_str(separator);
+        shown.push_str(model);
+    }
+
+    shown
+}
+
+fn format_provider_stat(stat: &ProviderStat) -> String {
+    let mut lines = vec![format!(
+        "Requests: {} ({} failed)",
+        stat.requests, stat.failures
+    )];
+
+    if let Some(latency) = stat.average_latency() {
+        lines.push(format!("Average latency: {} ms", latency.as_millis()));
+    }
+
+    if let Some(last_success) = stat.last_success {
+        lines.push(format!("Last success: <t:{}:R>", last_success.timestamp()));
+    }
+
+    if let Some((last_error_at, error)) = &stat.last_error {
+        lines.push(format!(
+            "Last error: <t:{}:R> {}",
+            last_error_at.timestamp(),
+            format_error(error)
+        ));
+    }
+
+    lines.join("\n")
+}
+
+pub struct Status;
+
+impl Status {
+    /// The stage and latency of every shard in this process. The shard of the command is in bold
+    async fn shards(&self, cmd: &CommandContext<'_>) -> String {
+        let data = cmd.ctx.data.read().await;
+        let Some(shard_manager) = data.get::<ShardManagerContainer>() else {
+            return "Unknown".to_string();
+        };
+
+        let runners = shard_manager.runners.lock().await;
+        let mut shards: Vec<_> = runners.iter().collect();
+        shards.sort_by_key(|(shard_id, _)| shard_id.0);
+
+        let lines: Vec<String> = shards
+            .into_iter()
+            .map(|(shard_id, runner)| {
+                let latency = match runner.latency {
+                    Some(latency) => format!("{} ms", latency.as_millis()),
+                    None => "unknown latency".to_string(),
+                };
+                let line = format!("Shard {}: {}, {}", shard_id, runner.stage, latency);
+
+                if *shard_id == cmd.ctx.shard_id {
+                    format!("**{}**", line)
+                } else {
+                    line
+                }
+            })
+            .collect();
+
+        format!(
+            "{}\nTotal shards: {}",
+            lines.join("\n"),
+            cmd.ctx.cache.shard_count()
+        )
+    }
+
+    fn ollama_status(&self, cmd: &CommandContext<'_>) -> String {
+        let ollama_health = cmd.state.ai_dolly.ollama_health();
+        let status = ollama_health.status();
+
+        let state = if status.checked_at.is_none() {
+            "⏳ Not checked yet"
+        } else if status.pulling {
+            "⏳ Pulling the model"
+        } else if !status.reachable {
+            "🔴 Not reachable"
+        } else if !status.model_available {
+            "🟠 Model missing"
+        } else {
+            "🟢 Available"
+        };
+
+        let mut ollama = format!("{}\nModel: `{}`", state, ollama_health.model());
+
+        if let Some(error) = &status.error {
+            ollama.push_str(&format!("\nError: {}", format_error(error)));
+        }
+
+        if status.reachable && !status.model_available && !status.models.is_empty() {
+            ollama.push_str(&format!(
+                "\nAvailable models: {}",
+                format_models(&status.models)
+            ));
+        }
+
+        if let Some(checked_at) = status.checked_at {
+            ollama.push_str(&format!("\nLast checked: <t:{}:R>", checked_at.timestamp()));
+        }
+
+        ollama
+    }
+}
+
+#[async_trait]
+impl SlashCommand for Status {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Shows the uptime, latency and if the AI providers are working")
+            .description_localized(
+                DUTCH,
+                "Toont de uptime, latency en of de AI-providers werken",
+            )
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        let ai_dolly = &cmd.state.ai_dolly;
+        let provider_stats = ai_dolly.provider_stats();
+
+        let shards = self.shards(cmd).await;
+
+        let active_provider = match provider_stats.last_used() {
+            Some((provider, model)) => format!("{:?} (`{}`)", provider, model),
+            None => "None yet".to_string(),
+        };
+
+        let (messages, participants, max_messages) = ai_dolly.conversation_stats();
+        let max_messages = match max_messages {
+            0 => "no limit".to_string(),
+            max => format!("max {}", max),
+        };
+
+        let mut embed = CreateEmbed::new()
+            .title("Status")
+            .field(
+                "Uptime",
+                format_duration(cmd.state.started_at.elapsed()),
+                true,
+            )
+            .field("Active provider", active_provider, true)
+            .field("Shards", shards, false)
+            .field(
+                "Conversation",
+                format!(
+                    "{} stored messages ({})\n{} participants",
+                    messages, max_messages, participants
+                ),
+                false,
+            );
+
+        for provider in ai_dolly.configured_providers() {
+            let mut value = format_provider_stat(&provider_stats.get(provider));
+
+            if provider == Provider::Ollama {
+                value = format!("{}\n{}", self.ollama_status(cmd), value);
+            }
+
+            embed = embed.field(format!("{:?}", provider), value, false);
+        }
+
+        CommandResponse::embed(embed)
+    }
+}
@@ -1,5 +1,6 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
 use std::fs::read_to_string;
 
 struct CroppedString {
@@ -31,22 +32,34 @@ fn crop_string(input_string: &str, limit: usize) -> CroppedString {
     }
 }
 
-pub fn run(_options: &[ResolvedOption]) -> String {
-    let mut system_message = read_to_string("system_message.txt").unwrap_or("".to_string());
+pub struct SystemPrompt;
 
-    if !system_message.is_empty() {
-        let cut_system_message = crop_string(&system_message, 1500);
+#[async_trait]
+impl SlashCommand for SystemPrompt {
+    fn name(&self) -> &'static str {
+        "system_prompt"
+    }
 
-        return format!(
-            "{} **and {} more chars**",
-            cut_system_message.content, cut_system_message.cut_amount
-        );
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "systeemprompt")
+            .description("Shows the current system prompt")
+            .description_localized(DUTCH, "Toont de huidige systeemprompt")
     }
 
-    system_message = read_to_string("system_message_example.txt").unwrap();
-    system_message
-}
+    async fn run(&self, _cmd: &CommandContext<'_>) -> CommandResponse {
+        let mut system_message = read_to_string("system_message.txt").unwrap_or("".to_string());
+
+        if !system_message.is_empty() {
+            let cut_system_message = crop_string(&system_message, 1500);
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("system_prompt").description("Shows the current system prompt")
+            return CommandResponse::text(format!(
+                "{} **and {} more chars**",
+                cut_system_message.content, cut_system_message.cut_amount
+            ));
+        }
+
+        system_message = read_to_string("system_message_example.txt").unwrap();
+        CommandResponse::text(system_message)
+    }
 }
@@ -0,0 +1,183 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::messages::{SettingsScope, Trigger};
+use serenity::all::{CommandOptionType, CreateCommandOption, ResolvedOption, ResolvedValue};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::Permissions;
+
+/// Creates the `scope` option that is shared between the settings commands
+pub fn scope_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "scope",
+        "Whether it applies to the whole server or only this channel. Defaults to server.",
+    )
+    .name_localized(DUTCH, "bereik")
+    .description_localized(
+        DUTCH,
+        "Of het voor de hele server of alleen dit kanaal geldt. Standaard de server.",
+    )
+    .add_string_choice("server", "guild")
+    .add_string_choice_localized("channel", "channel", [(DUTCH, "kanaal")])
+}
+
+/// Gets the scope from the `scope` option. Direct messages always use the channel scope
+///
+/// # Arguments
+///
+/// * `cmd` - The context and interaction of the command
+/// * `options` - The options the user filled in
+pub fn get_scope(cmd: &CommandContext<'_>, options: &[ResolvedOption<'_>]) -> SettingsScope {
+    let wants_channel = options.iter().any(|option| {
+        option.name == "scope" && matches!(option.value, ResolvedValue::String("channel"))
+    });
+
+    match cmd.command.guild_id {
+        Some(guild_id) if !wants_channel => SettingsScope::Guild(guild_id),
+        _ => SettingsScope::Channel(cmd.command.channel_id),
+    }
+}
+
+pub struct Triggers;
+
+#[async_trait]
+impl SlashCommand for Triggers {
+    fn name(&self) -> &'static str {
+        "triggers"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_GUILD
+    }
+
+    fn register(&self) -> CreateCommand {
+        let trigger_option = || {
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "trigger",
+                "The trigger word. Use = for exact matches, re: for regex and cs: for case-sensitive.",
+            )
+                .description_localized(
+                    DUTCH,
+                    "Het triggerwoord. Gebruik = voor exacte matches, re: voor regex en cs: voor hoofdlettergevoelig.",
+                )
+                .required(true)
+        };
+
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "triggerwoorden")
+            .description("Manages the words I respond to")
+            .description_localized(DUTCH, "Beheert de woorden waarop ik reageer")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Adds a trigger word",
+                )
+                .name_localized(DUTCH, "toevoegen")
+                .description_localized(DUTCH, "Voegt een triggerwoord toe")
+                .add_sub_option(trigger_option())
+                .add_sub_option(scope_option()),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Removes a trigger word",
+                )
+                .name_localized(DUTCH, "verwijderen")
+                .description_localized(DUTCH, "Verwijdert een triggerwoord")
+                .add_sub_option(trigger_option())
+                .add_sub_option(scope_option()),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "Lists the trigger words of this channel",
+                )
+                .name_localized(DUTCH, "lijst")
+                .description_localized(DUTCH, "Toont de triggerwoorden van dit kanaal"),
+            )
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        let options = cmd.options();
+        let Some(subcommand) = options.first() else {
+            return CommandResponse::text("Error: no subcommand provided").ephemeral();
+        };
+
+        let ResolvedValue::SubCommand(sub_options) = &subcommand.value else {
+            return CommandResponse::text("Error: no subcommand provided").ephemeral();
+        };
+
+        let settings = cmd.state.ai_dolly.response_settings();
+        let guild_id = cmd.command.guild_id;
+        let channel_id = cmd.command.channel_id;
+
+        if subcommand.name == "list" {
+            let triggers = settings.trigger_list(guild_id, channel_id);
+
+            if triggers.is_empty() {
+                return CommandResponse::text("There are no triggers in this channel").ephemeral();
+            }
+
+            let list = triggers
+                .iter()
+                .map(|trigger| format!("- `{}`", trigger))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            return CommandResponse::text(format!("I respond to:\n{}", list)).ephemeral();
+        }
+
+        let mut trigger = String::new();
+        for option in sub_options {
+            if option.name == "trigger"
+                && let ResolvedValue::String(value) = &option.value
+            {
+                trigger = value.trim().to_string();
+            }
+        }
+
+        if trigger.is_empty() {
+            return CommandResponse::text("Error: no trigger provided").ephemeral();
+        }
+
+        let scope = get_scope(cmd, sub_options);
+
+        match subcommand.name {
+            "add" => {
+                if let Err(why) = Trigger::parse(&trigger) {
+                    return CommandResponse::text(format!(
+                        "`{}` is not a valid trigger: {}",
+                        trigger, why
+                    ))
+                    .ephemeral();
+                }
+
+                if settings.add_trigger(scope, guild_id, channel_id, &trigger) {
+                    CommandResponse::text(format!(
+                        "I will now respond to `{}` in {}",
+                        trigger,
+                        scope.name()
+                    ))
+                } else {
+                    CommandResponse::text(format!("`{}` is already a trigger", trigger)).ephemeral()
+                }
+            }
+            "remove" => {
+                if settings.remove_trigger(scope, guild_id, channel_id, &trigger) {
+                    CommandResponse::text(format!(
+                        "I will no longer respond to `{}` in {}",
+                        trigger,
+                        scope.name()
+                    ))
+                } else {
+                    CommandResponse::text(format!("`{}` is not a trigger", trigger)).ephemeral()
+                }
+            }
+            _ => CommandResponse::text("not implemented :(").ephemeral(),
+        }
+    }
+}
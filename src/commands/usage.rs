@@ -0,0 +1,61 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use crate::messages::UsageTotals;
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateEmbed};
+
+fn format_totals(totals: &UsageTotals) -> String {
+    format!(
+        "{} requests\n{} input tokens\n{} output tokens\n${:.4}",
+        totals.requests, totals.input_tokens, totals.output_tokens, totals.cost
+    )
+}
+
+pub struct Usage;
+
+#[async_trait]
+impl SlashCommand for Usage {
+    fn name(&self) -> &'static str {
+        "usage"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "gebruik")
+            .description("Shows the AI token usage and cost of today")
+            .description_localized(DUTCH, "Toont het AI-tokengebruik en de kosten van vandaag")
+    }
+
+    async fn run(&self, cmd: &CommandContext<'_>) -> CommandResponse {
+        let today = cmd.state.ai_dolly.usage_tracker().today();
+
+        let mut embed = CreateEmbed::new().title("AI usage of today").field(
+            "Total",
+            format_totals(&today.total),
+            false,
+        );
+
+        let mut providers: Vec<_> = today.providers.iter().collect();
+        providers.sort_by(|a, b| a.0.cmp(b.0));
+        for (provider, totals) in providers {
+            embed = embed.field(provider, format_totals(totals), true);
+        }
+
+        if let Some(guild_id) = cmd.command.guild_id {
+            let guild = today
+                .guilds
+                .get(&guild_id.to_string())
+                .cloned()
+                .unwrap_or_default();
+            embed = embed.field("This server", format_totals(&guild), false);
+        }
+
+        let user = today
+            .users
+            .get(&cmd.command.user.id.to_string())
+            .cloned()
+            .unwrap_or_default();
+        embed = embed.field("You", format_totals(&user), false);
+
+        CommandResponse::embed(embed).ephemeral()
+    }
+}
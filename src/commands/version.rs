@@ -1,12 +1,25 @@
+use super::{CommandContext, CommandResponse, DUTCH, SlashCommand};
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
-use serenity::model::application::ResolvedOption;
 use std::env;
 
-pub fn run(_options: &[ResolvedOption]) -> String {
-    let version = env!("CARGO_PKG_VERSION").to_string();
-    format!("My version is: `v{}`", version)
-}
+pub struct Version;
+
+#[async_trait]
+impl SlashCommand for Version {
+    fn name(&self) -> &'static str {
+        "version"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .name_localized(DUTCH, "versie")
+            .description("Shows my current version")
+            .description_localized(DUTCH, "Toont mijn huidige versie")
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("version").description("Shows my current version")
+    async fn run(&self, _cmd: &CommandContext<'_>) -> CommandResponse {
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        CommandResponse::text(format!("My version is: `v{}`", version))
+    }
 }
@@ -0,0 +1,40 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Makes the temporary files of writes that happen at the same time unique
+static TEMPORARY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Writes a file by writing a temporary file next to it and renaming that over it, so the file
+/// is never left half written when the bot stops in the middle. Every write gets its own
+/// temporary file, so two writes to the same file can't mix their content
+///
+/// # Arguments
+///
+/// * `path` - The file to write
+/// * `content` - The new content of the file
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMPORARY_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = write_and_rename(Path::new(&temporary), path, content);
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+fn write_and_rename(temporary: &Path, path: &Path, content: &str) -> std::io::Result<()> {
+    let mut file = fs::File::create_new(temporary)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(temporary, path)
+}
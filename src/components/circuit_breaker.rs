@@ -0,0 +1,228 @@
+use super::{ConfigReader, Logger, Severity};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// The state of a circuit breaker
+///
+/// # Fields
+///
+/// * `failures` - The amount of failures in a row
+/// * `open_until` - When the service can be tried again. `None` when it's closed
+/// * `testing_since` - When the one request that checks if the service works again was let
+///   through after the cooldown. `None` when no request is checking it
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    testing_since: Option<Instant>,
+}
+
+/// When a service is skipped
+///
+/// # Fields
+///
+/// * `failure_threshold` - The amount of failures in a row before a service is skipped from
+///   `CIRCUIT_BREAKER_THRESHOLD`
+/// * `cooldown` - How long a service is skipped from `CIRCUIT_BREAKER_COOLDOWN`
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerConfig {
+    /// Reads the threshold and cooldown from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        Self {
+            failure_threshold: reader.number("CIRCUIT_BREAKER_THRESHOLD", 3),
+            cooldown: Duration::from_secs(reader.number("CIRCUIT_BREAKER_COOLDOWN", 60)),
+        }
+    }
+}
+
+/// Skips a service for a while after it failed too many times in a row
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `name` - The name of the service
+/// * `config` - The threshold and cooldown, which can be replaced while running
+/// * `state` - The failures and when the service can be tried again
+pub struct CircuitBreaker {
+    logger: Logger,
+    name: String,
+    config: RwLock<CircuitBreakerConfig>,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the service
+    /// * `config` - The amount of failures in a row before the service is skipped and for how
+    ///   long. A threshold of `0` never skips it
+    pub fn new(name: &str, config: CircuitBreakerConfig) -> Self {
+        Self {
+            logger: Logger::new("CircuitBreaker"),
+            name: name.to_string(),
+            config: RwLock::new(config),
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Replaces the threshold and cooldown. A service that is being skipped stays skipped until
+    /// its current cooldown is over
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new threshold and cooldown
+    pub fn reload(&self, config: CircuitBreakerConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Checks if the service can be used. After the cooldown only one request is let through
+    /// to check if it works again, every other request is skipped until that one is recorded.
+    /// When it's never recorded, another request is let through after another cooldown
+    pub fn allows(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let config = *self.config.read().unwrap();
+
+        if let Some(testing_since) = state.testing_since {
+            if now < testing_since + config.cooldown {
+                return false;
+            }
+        } else {
+            match state.open_until {
+                Some(open_until) if now < open_until => return false,
+                Some(_) => {}
+                None => return true,
+            }
+        }
+
+        // Let one request through, a failure opens it again right away
+        state.open_until = None;
+        state.failures = config.failure_threshold.saturating_sub(1);
+        state.testing_since = Some(now);
+        true
+    }
+
+    /// Checks if the service is being skipped right now, without letting a request through
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    /// Marks the last request as successful
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.testing_since.is_some() {
+            self.logger.info(format!("{} is working again", self.name));
+        }
+
+        state.failures = 0;
+        state.open_until = None;
+        state.testing_since = None;
+    }
+
+    /// Marks the last request as failed and skips the service when it failed too often
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.testing_since = None;
+
+        let config = *self.config.read().unwrap();
+        if config.failure_threshold == 0 || state.failures < config.failure_threshold {
+            return;
+        }
+
+        state.open_until = Some(Instant::now() + config.cooldown);
+        self.logger.warning(
+            format!(
+                "{} failed {} times in a row. Skipping it for {} seconds",
+                self.name,
+                state.failures,
+                config.cooldown.as_secs()
+            )
+            .as_str(),
+            Severity::Medium,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const COOLDOWN: Duration = Duration::from_millis(50);
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(
+            "Test",
+            CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown: COOLDOWN,
+            },
+        );
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker
+    }
+
+    #[test]
+    fn skips_the_service_during_the_cooldown() {
+        let breaker = open_breaker();
+
+        assert!(breaker.is_open());
+        assert!(!breaker.allows());
+    }
+
+    #[test]
+    fn lets_one_request_through_after_the_cooldown() {
+        let breaker = open_breaker();
+        sleep(COOLDOWN);
+
+        assert!(breaker.allows());
+        assert!(!breaker.allows());
+        assert!(!breaker.allows());
+
+        breaker.record_success();
+        assert!(breaker.allows());
+        assert!(breaker.allows());
+    }
+
+    #[test]
+    fn opens_again_when_the_test_request_fails() {
+        let breaker = open_breaker();
+        sleep(COOLDOWN);
+
+        assert!(breaker.allows());
+        breaker.record_failure();
+
+        assert!(breaker.is_open());
+        assert!(!breaker.allows());
+    }
+
+    #[test]
+    fn lets_another_request_through_when_the_test_request_is_lost() {
+        let breaker = open_breaker();
+        sleep(COOLDOWN);
+
+        assert!(breaker.allows());
+        assert!(!breaker.allows());
+
+        sleep(COOLDOWN);
+        assert!(breaker.allows());
+        assert!(!breaker.allows());
+    }
+}
@@ -0,0 +1,482 @@
+use super::{ConfigReader, Logger};
+use serenity::model::Permissions;
+use serenity::model::application::CommandInteraction;
+use serenity::model::id::{GuildId, RoleId, UserId};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Who is allowed to use a command and in which guilds
+///
+/// # Fields
+///
+/// * `required_permissions` - The Discord permissions a member needs when no allow-list is set
+/// * `allowed_roles` - Role ids from `<COMMAND>_ALLOWED_ROLES`
+/// * `allowed_users` - User ids from `<COMMAND>_ALLOWED_USERS`
+/// * `enabled_guilds` - Guild ids from `<COMMAND>_ENABLED_GUILDS`. When set, the command only
+///   works in these guilds
+/// * `disabled_guilds` - Guild ids from `<COMMAND>_DISABLED_GUILDS` where the command is off
+#[derive(Debug, Clone, Default)]
+pub struct CommandRule {
+    pub required_permissions: Permissions,
+    pub allowed_roles: Vec<RoleId>,
+    pub allowed_users: Vec<UserId>,
+    pub enabled_guilds: Vec<GuildId>,
+    pub disabled_guilds: Vec<GuildId>,
+}
+
+impl CommandRule {
+    fn has_allow_list(&self) -> bool {
+        !self.allowed_roles.is_empty() || !self.allowed_users.is_empty()
+    }
+
+    /// If the command can be used in a guild. Direct messages only allow the commands that
+    /// aren't limited to some guilds
+    ///
+    /// # Arguments
+    ///
+    /// * `guild_id` - The guild, or `None` for direct messages
+    fn is_enabled_in(&self, guild_id: Option<GuildId>) -> bool {
+        match guild_id {
+            Some(guild_id) => {
+                (self.enabled_guilds.is_empty() || self.enabled_guilds.contains(&guild_id))
+                    && !self.disabled_guilds.contains(&guild_id)
+            }
+            None => self.enabled_guilds.is_empty(),
+        }
+    }
+}
+
+/// Reads the `<COMMAND>_ALLOWED_ROLES`, `<COMMAND>_ALLOWED_USERS`,
+/// `<COMMAND>_ENABLED_GUILDS` and `<COMMAND>_DISABLED_GUILDS` of every command. Other settings
+/// that happen to end the same way are ignored
+///
+/// # Arguments
+///
+/// * `reader` - The merged config
+/// * `command_names` - The names of the registered commands. Like `ping`
+pub fn read_command_rules(
+    reader: &mut ConfigReader,
+    command_names: &[&str],
+) -> HashMap<String, CommandRule> {
+    let mut rules = HashMap::new();
+
+    for command in command_names {
+        let prefix = command.to_uppercase();
+        let rule = CommandRule {
+            required_permissions: Permissions::empty(),
+            allowed_roles: reader
+                .id_list(&format!("{}_ALLOWED_ROLES", prefix))
+                .into_iter()
+                .map(RoleId::new)
+                .collect(),
+            allowed_users: reader
+                .id_list(&format!("{}_ALLOWED_USERS", prefix))
+                .into_iter()
+                .map(UserId::new)
+                .collect(),
+            enabled_guilds: reader
+                .id_list(&format!("{}_ENABLED_GUILDS", prefix))
+                .into_iter()
+                .map(GuildId::new)
+                .collect(),
+            disabled_guilds: reader
+                .id_list(&format!("{}_DISABLED_GUILDS", prefix))
+                .into_iter()
+                .map(GuildId::new)
+                .collect(),
+        };
+
+        if rule.has_allow_list()
+            || !rule.enabled_guilds.is_empty()
+            || !rule.disabled_guilds.is_empty()
+        {
+            rules.insert(command.to_string(), rule);
+        }
+    }
+
+    rules
+}
+
+/// Decides who can use which slash command
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `rules` - The rules per command name, which can be replaced while running
+pub struct CommandPermissions {
+    logger: Logger,
+    rules: RwLock<HashMap<String, CommandRule>>,
+}
+
+impl CommandPermissions {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The allow-lists per command name from the config
+    pub fn new(rules: HashMap<String, CommandRule>) -> Self {
+        let logger = Logger::new("CommandPermissions");
+
+        for (command, rule) in &rules {
+            logger.debug(format!("Permission rule for '{}': {:?}", command, rule).as_str());
+        }
+
+        Self {
+            logger,
+            rules: RwLock::new(rules),
+        }
+    }
+
+    /// Replaces the allow-lists. The Discord permissions and the enabled and disabled guilds
+    /// of the commands are kept, because they decide where the commands are registered
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The new allow-lists per command name from the config
+    pub fn reload(&self, mut rules: HashMap<String, CommandRule>) {
+        let mut current = self.rules.write().unwrap();
+
+        for rule in rules.values_mut() {
+            rule.enabled_guilds.clear();
+            rule.disabled_guilds.clear();
+        }
+
+        for (command, rule) in current.iter() {
+            let new_rule = rules.entry(command.clone()).or_default();
+            new_rule.required_permissions = rule.required_permissions;
+            new_rule.enabled_guilds = rule.enabled_guilds.clone();
+            new_rule.disabled_guilds = rule.disabled_guilds.clone();
+        }
+
+        *current = rules;
+    }
+
+    /// Sets the Discord permissions that a command requires
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The name of the command
+    /// * `permissions` - The permissions a member needs to use the command
+    pub fn require(&mut self, command: &str, permissions: Permissions) {
+        self.rules
+            .get_mut()
+            .unwrap()
+            .entry(command.to_string())
+            .or_default()
+            .required_permissions = permissions;
+    }
+
+    /// Checks if a command can be used in a guild
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The name of the command
+    /// * `guild_id` - The guild, or `None` for direct messages
+    pub fn is_enabled(&self, command: &str, guild_id: Option<GuildId>) -> bool {
+        self.rules
+            .read()
+            .unwrap()
+            .get(command)
+            .is_none_or(|rule| rule.is_enabled_in(guild_id))
+    }
+
+    /// If a command is limited to some guilds or turned off in some. Those commands are
+    /// registered per guild, because a global command shows up everywhere
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The name of the command
+    pub fn is_guild_limited(&self, command: &str) -> bool {
+        self.rules
+            .read()
+            .unwrap()
+            .get(command)
+            .is_some_and(|rule| !rule.enabled_guilds.is_empty() || !rule.disabled_guilds.is_empty())
+    }
+
+    /// If only the roles and users of `<COMMAND>_ALLOWED_ROLES` and `<COMMAND>_ALLOWED_USERS`
+    /// can use a command. Discord shouldn't hide those commands behind permissions, because
+    /// the allowed members often don't have them
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The name of the command
+    pub fn has_allow_list(&self, command: &str) -> bool {
+        self.rules
+            .read()
+            .unwrap()
+            .get(command)
+            .is_some_and(CommandRule::has_allow_list)
+    }
+
+    /// Every guild that is in a `<COMMAND>_ENABLED_GUILDS` list, so their commands can be
+    /// registered there
+    pub fn enabled_guilds(&self) -> Vec<GuildId> {
+        let mut guild_ids: Vec<GuildId> = self
+            .rules
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(|rule| rule.enabled_guilds.iter().copied())
+            .collect();
+
+        guild_ids.sort();
+        guild_ids.dedup();
+        guild_ids
+    }
+
+    /// The commands that are only turned off in some guilds. They can't be registered
+    /// globally, so they only exist in the guilds they're registered in one by one
+    pub fn disabled_only(&self) -> Vec<String> {
+        let mut commands: Vec<String> = self
+            .rules
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, rule)| rule.enabled_guilds.is_empty() && !rule.disabled_guilds.is_empty())
+            .map(|(command, _)| command.clone())
+            .collect();
+
+        commands.sort();
+        commands
+    }
+
+    /// Checks if the user of the interaction is allowed to use the command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The received command interaction
+    ///
+    /// # Returns
+    ///
+    /// The reason why the user is not allowed as an error
+    pub fn check(&self, command: &CommandInteraction) -> Result<(), String> {
+        let name = command.data.name.as_str();
+
+        let rules = self.rules.read().unwrap();
+        let Some(rule) = rules.get(name) else {
+            return Ok(());
+        };
+
+        if !rule.is_enabled_in(command.guild_id) {
+            self.logger.info(format!(
+                "Denied '{}' for {}: disabled in {:?}",
+                name, command.user.id, command.guild_id
+            ));
+            return Err("This command is disabled here".to_string());
+        }
+
+        if rule.allowed_users.contains(&command.user.id) {
+            return Ok(());
+        }
+
+        let member = command.member.as_deref();
+
+        if rule.has_allow_list() {
+            let has_allowed_role = member
+                .map(|member| {
+                    member
+                        .roles
+                        .iter()
+                        .any(|role| rule.allowed_roles.contains(role))
+                })
+                .unwrap_or(false);
+
+            if has_allowed_role {
+                return Ok(());
+            }
+
+            self.logger.info(format!(
+                "Denied '{}' for {}: not on the allow-list",
+                name, command.user.id
+            ));
+            return Err("You are not allowed to use this command".to_string());
+        }
+
+        if rule.required_permissions.is_empty() {
+            return Ok(());
+        }
+
+        // Discord already hides the command, but DMs and outdated registrations skip that check
+        match member.and_then(|member| member.permissions) {
+            Some(permissions) if permissions.contains(rule.required_permissions) => Ok(()),
+            Some(_) => {
+                self.logger.info(format!(
+                    "Denied '{}' for {}: missing {}",
+                    name, command.user.id, rule.required_permissions
+                ));
+                Err(format!(
+                    "You need the following permissions to use this command: {}",
+                    rule.required_permissions
+                ))
+            }
+            None => Err("This command can only be used inside of a server".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ADMINISTRATOR: &str = "8";
+
+    fn reader(values: &[(&str, &str)]) -> ConfigReader {
+        ConfigReader::new(
+            values
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    fn permissions(rules: &[(&str, &str)]) -> CommandPermissions {
+        let mut permissions =
+            CommandPermissions::new(read_command_rules(&mut reader(rules), &["ping", "rage"]));
+        permissions.require("rage", Permissions::ADMINISTRATOR);
+        permissions
+    }
+
+    /// A `/<name>` interaction from user 10 with role 20 in guild 30, or in a direct message
+    /// when `member_permissions` is `None`
+    fn interaction(name: &str, member_permissions: Option<&str>) -> CommandInteraction {
+        let user = json!({
+            "id": "10",
+            "username": "dolly",
+            "discriminator": "0",
+            "global_name": null,
+            "avatar": null,
+        });
+
+        let mut interaction = json!({
+            "id": "1",
+            "application_id": "2",
+            "type": 2,
+            "data": { "id": "3", "name": name, "type": 1 },
+            "channel_id": "4",
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+            "entitlements": [],
+            "attachment_size_limit": 8388608,
+        });
+
+        match member_permissions {
+            Some(member_permissions) => {
+                interaction["guild_id"] = json!("30");
+                interaction["member"] = json!({
+                    "user": user,
+                    "roles": ["20"],
+                    "joined_at": "2024-01-01T00:00:00Z",
+                    "deaf": false,
+                    "mute": false,
+                    "flags": 0,
+                    "permissions": member_permissions,
+                });
+            }
+            None => interaction["user"] = user,
+        }
+
+        serde_json::from_value(interaction).unwrap()
+    }
+
+    #[test]
+    fn reads_the_rules_of_registered_commands() {
+        let mut reader = reader(&[
+            ("PING_ALLOWED_ROLES", "20, 21"),
+            ("PING_ALLOWED_USERS", "10"),
+            ("RAGE_ENABLED_GUILDS", "30"),
+            ("RAGE_DISABLED_GUILDS", "31"),
+        ]);
+
+        let rules = read_command_rules(&mut reader, &["ping", "rage", "quote"]);
+
+        assert!(reader.errors().is_empty());
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            rules["ping"].allowed_roles,
+            [RoleId::new(20), RoleId::new(21)]
+        );
+        assert_eq!(rules["ping"].allowed_users, [UserId::new(10)]);
+        assert_eq!(rules["rage"].enabled_guilds, [GuildId::new(30)]);
+        assert_eq!(rules["rage"].disabled_guilds, [GuildId::new(31)]);
+    }
+
+    #[test]
+    fn ignores_settings_of_other_commands() {
+        let mut reader = reader(&[
+            ("BACKUP_ALLOWED_ROLES", "admins"),
+            ("FEATURE_ENABLED_GUILDS", "everywhere"),
+        ]);
+
+        let rules = read_command_rules(&mut reader, &["ping"]);
+
+        assert!(reader.errors().is_empty());
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_ids() {
+        let mut reader = reader(&[("PING_ALLOWED_USERS", "10, dolly")]);
+
+        read_command_rules(&mut reader, &["ping"]);
+
+        assert_eq!(reader.errors().len(), 1);
+    }
+
+    #[test]
+    fn allows_commands_without_rules() {
+        let permissions = permissions(&[]);
+
+        assert!(permissions.check(&interaction("ping", Some("0"))).is_ok());
+        assert!(permissions.check(&interaction("ping", None)).is_ok());
+    }
+
+    #[test]
+    fn requires_the_permissions_of_the_command() {
+        let permissions = permissions(&[]);
+
+        assert!(permissions.check(&interaction("rage", Some("0"))).is_err());
+        assert!(permissions.check(&interaction("rage", None)).is_err());
+        assert!(
+            permissions
+                .check(&interaction("rage", Some(ADMINISTRATOR)))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn allow_lists_replace_the_permissions() {
+        let by_user = permissions(&[("RAGE_ALLOWED_USERS", "10")]);
+        let by_role = permissions(&[("RAGE_ALLOWED_ROLES", "20")]);
+        let by_other_role = permissions(&[("RAGE_ALLOWED_ROLES", "21")]);
+
+        assert!(by_user.check(&interaction("rage", Some("0"))).is_ok());
+        assert!(by_user.check(&interaction("rage", None)).is_ok());
+        assert!(by_role.check(&interaction("rage", Some("0"))).is_ok());
+        assert!(by_role.check(&interaction("rage", None)).is_err());
+        assert!(
+            by_other_role
+                .check(&interaction("rage", Some(ADMINISTRATOR)))
+                .is_err()
+        );
+        assert!(by_user.has_allow_list("rage"));
+        assert!(!by_user.has_allow_list("ping"));
+    }
+
+    #[test]
+    fn denies_commands_in_disabled_guilds() {
+        let disabled = permissions(&[("PING_DISABLED_GUILDS", "30")]);
+        let enabled_elsewhere = permissions(&[("PING_ENABLED_GUILDS", "31")]);
+
+        assert!(disabled.check(&interaction("ping", Some("0"))).is_err());
+        assert!(disabled.check(&interaction("ping", None)).is_ok());
+        assert!(
+            enabled_elsewhere
+                .check(&interaction("ping", Some("0")))
+                .is_err()
+        );
+        assert!(enabled_elsewhere.check(&interaction("ping", None)).is_err());
+    }
+}
@@ -0,0 +1,825 @@
+use super::{
+    CircuitBreakerConfig, CommandRule, DotEnvReader, HttpConfig, Logger, RateLimiterConfig,
+    RetryPolicy, Severity, Sharding, is_secret, parse_id_list, read_command_rules,
+    read_secret_files, redact, remember_secrets,
+};
+use crate::messages::{
+    CooldownReply, OllamaConfig, ProviderConfig, ReplyPolicyConfig, ResponseCacheConfig,
+    UsageConfig, split_triggers,
+};
+use serenity::model::id::GuildId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::read_to_string;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Settings that are only used on startup, so changing them needs a restart
+const RESTART_REQUIRED: [&str; 10] = [
+    "DISCORD_TOKEN",
+    "GUILD_ID",
+    "GUILD_IDS",
+    "LISTEN_ADDR",
+    "SHARDS",
+    "HTTP_USER_AGENT",
+    "HTTP_PROXY_URL",
+    "HTTP_CA_BUNDLE",
+    "HTTP_TIMEOUT",
+    "HTTP_HOST_TIMEOUTS",
+];
+
+/// Settings like `RAGE_DISABLED_GUILDS` that decide where commands are registered, so
+/// changing them needs a restart as well
+const RESTART_REQUIRED_SUFFIXES: [&str; 2] = ["_ENABLED_GUILDS", "_DISABLED_GUILDS"];
+
+/// Reads typed settings from the merged values and collects every invalid one, so they can
+/// be reported together
+///
+/// # Fields
+///
+/// * `values` - The settings by their environment variable name. Like `OLLAMA_URL`
+/// * `errors` - Why settings are invalid
+/// * `read_keys` - The names of the settings that have been asked for, so the rest of the
+///   environment can be left out
+pub struct ConfigReader {
+    values: HashMap<String, String>,
+    errors: Vec<String>,
+    read_keys: BTreeSet<String>,
+}
+
+impl ConfigReader {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The settings by their environment variable name
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Self {
+            values,
+            errors: Vec::new(),
+            read_keys: BTreeSet::new(),
+        }
+    }
+
+    /// Marks the config as invalid
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - Why the config is invalid
+    pub fn error(&mut self, error: String) {
+        self.errors.push(error);
+    }
+
+    /// Why settings are invalid
+    #[cfg(test)]
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// A setting that has been set and isn't empty
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    pub fn optional(&mut self, key: &str) -> Option<String> {
+        self.read_keys.insert(key.to_string());
+
+        self.values
+            .get(key)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// A text setting
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    /// * `default` - Used when the setting is missing or empty
+    pub fn string(&mut self, key: &str, default: &str) -> String {
+        self.optional(key).unwrap_or_else(|| default.to_string())
+    }
+
+    /// A `true` or `false` setting
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    /// * `default` - Used when the setting is missing or empty
+    pub fn bool(&mut self, key: &str, default: bool) -> bool {
+        self.parse(key, "true or false", |value| {
+            match value.to_lowercase().as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            }
+        })
+        .unwrap_or(default)
+    }
+
+    /// A number setting
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    /// * `default` - Used when the setting is missing or empty
+    pub fn number<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        self.parse(key, "a whole number", |value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// A setting with its own format. Returns `None` when it's missing, empty or invalid
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    /// * `expected` - What a valid value looks like, used in the error
+    /// * `parse` - Turns the value into the setting. `None` means it's invalid
+    pub fn parse<T>(
+        &mut self,
+        key: &str,
+        expected: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Option<T> {
+        let value = self.optional(key)?;
+        let parsed = parse(&value);
+
+        if parsed.is_none() {
+            self.error(format!(
+                "{} '{}' is invalid. Expected {}",
+                key,
+                redact(key, &value),
+                expected
+            ));
+        }
+
+        parsed
+    }
+
+    /// A comma separated setting where every entry has its own format. Invalid entries are
+    /// left out
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    /// * `expected` - What a valid entry looks like, used in the error
+    /// * `parse` - Turns an entry into a value. `None` means it's invalid
+    pub fn list<T>(
+        &mut self,
+        key: &str,
+        expected: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Vec<T> {
+        let mut values = Vec::new();
+
+        for entry in self.string(key, "").split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            match parse(entry) {
+                Some(value) => values.push(value),
+                None => self.error(format!(
+                    "{} contains an invalid entry '{}'. Expected {}",
+                    key,
+                    redact(key, entry),
+                    expected
+                )),
+            }
+        }
+
+        values
+    }
+
+    /// A comma separated list of Discord ids
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the setting
+    pub fn id_list(&mut self, key: &str) -> Vec<u64> {
+        match parse_id_list(&self.string(key, "")) {
+            Ok(ids) => ids,
+            Err(id) => {
+                self.error(format!("{} contains an invalid id: '{}'", key, id));
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Every setting of the bot
+///
+/// # Fields
+///
+/// * `discord_token` - The token of the bot from `DISCORD_TOKEN`
+/// * `guild_ids` - The guilds that get the commands right away from `GUILD_IDS` and the older
+///   `GUILD_ID`
+/// * `listen_addr` - Where the metrics and health checks are served from `LISTEN_ADDR`
+/// * `sharding` - Which shards this process runs from `SHARDS`
+/// * `allow_changing_system_prompt` - If `/change_system_prompt` works from
+///   `ALLOW_CHANGING_SYSTEM_PROMPT`
+/// * `ollama` - The Ollama server
+/// * `openai` - The OpenAI token and model
+/// * `cohere` - The Cohere token and model
+/// * `prioritize_ollama` - If Ollama is tried before the paid providers from `PRIORTIZE_OLLAMA`
+/// * `responds_to` - The default triggers from `RESPONDS_TO`
+/// * `respond_to_all_messages` - If the default mode is replying to everything from
+///   `RESPOND_TO_ALL_MESSAGES`
+/// * `max_stored_messages` - The max messages of the conversation from `MAX_STORED_MESSAGES`
+/// * `cooldown_reply` - How rate limited people are told from `RATE_LIMIT_REPLY`
+/// * `rate_limits` - How often the AI can be used
+/// * `reply_policy` - Where the AI can reply
+/// * `usage` - The prices and budgets
+/// * `response_cache` - How long responses are reused
+/// * `http` - The proxy, certificates and timeouts of the HTTP client
+/// * `retry` - How failed requests are retried
+/// * `circuit_breaker` - When providers are skipped after failing
+/// * `command_rules` - Who can use which command in which guild from
+///   `<COMMAND>_ALLOWED_ROLES`, `<COMMAND>_ALLOWED_USERS`, `<COMMAND>_ENABLED_GUILDS` and
+///   `<COMMAND>_DISABLED_GUILDS`
+/// * `shutdown_timeout` - How long a shutdown waits for replies in progress from
+///   `SHUTDOWN_TIMEOUT`
+/// * `values` - Every known setting as it was read, used to show what changed on a reload.
+///   The rest of the environment isn't kept
+pub struct Config {
+    pub discord_token: String,
+    pub guild_ids: Vec<GuildId>,
+    pub listen_addr: Option<SocketAddr>,
+    pub sharding: Sharding,
+    pub allow_changing_system_prompt: bool,
+    pub ollama: OllamaConfig,
+    pub openai: ProviderConfig,
+    pub cohere: ProviderConfig,
+    pub prioritize_ollama: bool,
+    pub responds_to: Vec<String>,
+    pub respond_to_all_messages: bool,
+    pub max_stored_messages: i32,
+    pub cooldown_reply: CooldownReply,
+    pub rate_limits: RateLimiterConfig,
+    pub reply_policy: ReplyPolicyConfig,
+    pub usage: UsageConfig,
+    pub response_cache: ResponseCacheConfig,
+    pub http: HttpConfig,
+    pub retry: RetryPolicy,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub command_rules: HashMap<String, CommandRule>,
+    pub shutdown_timeout: Duration,
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Loads the config from `dolly.toml` (or `DOLLY_CONFIG`), then `.env` and then the
+    /// environment. Later sources win
+    ///
+    /// # Arguments
+    ///
+    /// * `environment` - The environment from before `.env` was applied to it, so a reload
+    ///   picks up the changes in `.env`
+    /// * `command_names` - The names of the slash commands, used for their permission settings
+    ///
+    /// # Returns
+    ///
+    /// Every invalid setting as an error
+    pub fn load(
+        environment: &HashMap<String, String>,
+        command_names: &[&str],
+    ) -> Result<Self, Vec<String>> {
+        let logger = Logger::new("Config");
+        let mut errors = Vec::new();
+
+        let config_file = environment
+            .get("DOLLY_CONFIG")
+            .cloned()
+            .unwrap_or_else(|| "dolly.toml".to_string());
+        let file_values = match read_to_string(&config_file) {
+            Ok(content) => match Self::parse_toml(&content) {
+                Ok(values) => {
+                    logger.info(format!("Loaded {}", config_file));
+                    values
+                }
+                Err(why) => {
+                    errors.push(format!("Could not parse '{}': {}", config_file, why));
+                    HashMap::new()
+                }
+            },
+            Err(_) => {
+                logger.debug(format!("No config file at '{}'", config_file).as_str());
+                HashMap::new()
+            }
+        };
+
+        let sources = [
+            file_values,
+            DotEnvReader::new(".env", environment).read(),
+            environment.clone(),
+        ];
+
+        Self::merge(sources, errors, &logger, command_names)
+    }
+
+    /// Merges the settings of every source and reads them. Later sources win
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The settings of `dolly.toml`, `.env` and the environment
+    /// * `errors` - Why the sources couldn't be loaded
+    /// * `logger` - Used for logging information and errors
+    /// * `command_names` - The names of the slash commands, used for their permission settings
+    fn merge(
+        sources: [HashMap<String, String>; 3],
+        mut errors: Vec<String>,
+        logger: &Logger,
+        command_names: &[&str],
+    ) -> Result<Self, Vec<String>> {
+        let mut values = HashMap::new();
+        for source in sources {
+            values.extend(source);
+        }
+
+        // Docker secrets like DISCORD_TOKEN_FILE
+        if let Err(file_errors) = read_secret_files(&mut values) {
+            errors.extend(file_errors);
+        }
+        remember_secrets(&values);
+
+        let mut reader = ConfigReader::new(values);
+        reader.errors = errors;
+
+        let mut config = Self::read(&mut reader, logger, command_names);
+        config.values = reader
+            .values
+            .into_iter()
+            .filter(|(key, _)| reader.read_keys.contains(key))
+            .collect();
+
+        if reader.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(reader.errors)
+        }
+    }
+
+    fn read(reader: &mut ConfigReader, logger: &Logger, command_names: &[&str]) -> Self {
+        let discord_token = reader.string("DISCORD_TOKEN", "");
+        if discord_token.is_empty() {
+            reader.error("DISCORD_TOKEN has not been set".to_string());
+        }
+
+        let guild_id = reader.parse("GUILD_ID", "a Discord id", |value| {
+            value.parse::<u64>().ok().filter(|id| *id != 0)
+        });
+
+        let mut guild_ids = reader.id_list("GUILD_IDS");
+        if let Some(guild_id) = guild_id.filter(|id| !guild_ids.contains(id)) {
+            guild_ids.insert(0, guild_id);
+        }
+
+        let listen_addr = reader.parse("LISTEN_ADDR", "an address like 0.0.0.0:9090", |value| {
+            value.parse::<SocketAddr>().ok()
+        });
+
+        let cooldown_reply = reader
+            .parse(
+                "RATE_LIMIT_REPLY",
+                "reaction, message or none",
+                |value| match value.to_lowercase().as_str() {
+                    "reaction" => Some(CooldownReply::Reaction),
+                    "message" => Some(CooldownReply::Message),
+                    "none" => Some(CooldownReply::Silent),
+                    _ => None,
+                },
+            )
+            .unwrap_or(CooldownReply::Reaction);
+
+        let config = Self {
+            discord_token,
+            guild_ids: guild_ids.into_iter().map(GuildId::new).collect(),
+            listen_addr,
+            sharding: Sharding::read(reader),
+            allow_changing_system_prompt: reader.bool("ALLOW_CHANGING_SYSTEM_PROMPT", false),
+            ollama: OllamaConfig::read(reader),
+            openai: ProviderConfig::read(reader, "OPENAI", "gpt-4o", 60),
+            cohere: ProviderConfig::read(reader, "COHERE", "command-r-plus-08-2024", 60),
+            prioritize_ollama: reader.bool("PRIORTIZE_OLLAMA", true),
+            responds_to: split_triggers(&reader.string("RESPONDS_TO", "dolly")),
+            respond_to_all_messages: reader.bool("RESPOND_TO_ALL_MESSAGES", false),
+            max_stored_messages: reader.number("MAX_STORED_MESSAGES", 6),
+            cooldown_reply,
+            rate_limits: RateLimiterConfig::read(reader),
+            reply_policy: ReplyPolicyConfig::read(reader),
+            usage: UsageConfig::read(reader),
+            response_cache: ResponseCacheConfig::read(reader),
+            http: HttpConfig::read(reader),
+            retry: RetryPolicy::read(reader),
+            circuit_breaker: CircuitBreakerConfig::read(reader),
+            command_rules: read_command_rules(reader, command_names),
+            shutdown_timeout: Duration::from_secs(reader.number("SHUTDOWN_TIMEOUT", 30)),
+            values: BTreeMap::new(),
+        };
+
+        if config.ollama.url.is_empty() {
+            logger.warning(
+                "OLLAMA_URL has not been set. The AI won't respond",
+                Severity::High,
+            );
+        }
+
+        config
+    }
+
+    /// Describes every setting that is different in the new config. The values of secrets
+    /// are left out
+    ///
+    /// # Arguments
+    ///
+    /// * `new` - The config that replaces this one
+    pub fn changes(&self, new: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        for (key, old_value) in &self.values {
+            match new.values.get(key) {
+                Some(new_value) if new_value == old_value => {}
+                Some(_) if is_secret(key) => changes.push(format!("{} changed", key)),
+                Some(new_value) => changes.push(format!(
+                    "{} changed from '{}' to '{}'",
+                    key, old_value, new_value
+                )),
+                None => changes.push(format!("{} was removed", key)),
+            }
+        }
+
+        for (key, new_value) in &new.values {
+            if self.values.contains_key(key) {
+                continue;
+            }
+
+            if is_secret(key) {
+                changes.push(format!("{} was added", key));
+            } else {
+                changes.push(format!("{} was added as '{}'", key, new_value));
+            }
+        }
+
+        changes
+    }
+
+    /// The changed settings that are only used on startup
+    ///
+    /// # Arguments
+    ///
+    /// * `new` - The config that replaces this one
+    pub fn restart_required(&self, new: &Config) -> Vec<String> {
+        self.startup_keys(new)
+            .into_iter()
+            .filter(|key| self.values.get(key) != new.values.get(key))
+            .collect()
+    }
+
+    /// Takes the settings that are only used on startup from the running config. The
+    /// running bot still uses those, so this config shouldn't claim the new values, and the
+    /// next reload warns about them again until the bot is restarted
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The config the bot was started with
+    pub fn keep_startup_settings(&mut self, current: &Config) {
+        for key in self.startup_keys(current) {
+            match current.values.get(&key) {
+                Some(value) => self.values.insert(key, value.clone()),
+                None => self.values.remove(&key),
+            };
+        }
+
+        self.discord_token = current.discord_token.clone();
+        self.guild_ids = current.guild_ids.clone();
+        self.listen_addr = current.listen_addr;
+        self.sharding = current.sharding;
+        self.http = current.http.clone();
+
+        for rule in self.command_rules.values_mut() {
+            rule.enabled_guilds.clear();
+            rule.disabled_guilds.clear();
+        }
+
+        for (command, rule) in &current.command_rules {
+            let new_rule = self.command_rules.entry(command.clone()).or_default();
+            new_rule.enabled_guilds = rule.enabled_guilds.clone();
+            new_rule.disabled_guilds = rule.disabled_guilds.clone();
+        }
+    }
+
+    /// The names of the settings that are only used on startup in either config
+    fn startup_keys(&self, other: &Config) -> BTreeSet<String> {
+        let command_keys = self
+            .command_rules
+            .keys()
+            .chain(other.command_rules.keys())
+            .flat_map(|command| {
+                RESTART_REQUIRED_SUFFIXES
+                    .iter()
+                    .map(move |suffix| format!("{}{}", command.to_uppercase(), suffix))
+            });
+
+        RESTART_REQUIRED
+            .into_iter()
+            .map(str::to_string)
+            .chain(command_keys)
+            .collect()
+    }
+
+    /// Turns the TOML file into settings by their environment variable name. Tables become a
+    /// prefix, so `url` in `[ollama]` is `OLLAMA_URL`. Lists become comma separated values
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the TOML file
+    fn parse_toml(content: &str) -> Result<HashMap<String, String>, String> {
+        let table = content
+            .parse::<toml::Table>()
+            .map_err(|why| why.message().to_string())?;
+
+        let mut values = HashMap::new();
+        Self::flatten_table("", &table, &mut values)?;
+        Ok(values)
+    }
+
+    fn flatten_table(
+        prefix: &str,
+        table: &toml::Table,
+        values: &mut HashMap<String, String>,
+    ) -> Result<(), String> {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, key.to_uppercase());
+
+            let value = match value {
+                toml::Value::Table(table) => {
+                    Self::flatten_table(&format!("{}_", key), table, values)?;
+                    continue;
+                }
+                toml::Value::Array(entries) => entries
+                    .iter()
+                    .map(|entry| {
+                        Self::toml_value(entry)
+                            .map(|entry| entry.replace(',', "\\,"))
+                            .ok_or_else(|| format!("{} can only contain plain values", key))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(","),
+                value => Self::toml_value(value)
+                    .ok_or_else(|| format!("{} has an unsupported value", key))?,
+            };
+
+            values.insert(key, value);
+        }
+
+        Ok(())
+    }
+
+    fn toml_value(value: &toml::Value) -> Option<String> {
+        match value {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Integer(value) => Some(value.to_string()),
+            toml::Value::Float(value) => Some(value.to_string()),
+            toml::Value::Boolean(value) => Some(value.to_string()),
+            toml::Value::Datetime(value) => Some(value.to_string()),
+            toml::Value::Array(_) | toml::Value::Table(_) => None,
+        }
+    }
+}
+
+/// The config that can be replaced while running, shared by everything that reads it
+///
+/// # Fields
+///
+/// * `current` - The config that is used right now
+pub struct SharedConfig {
+    current: RwLock<Arc<Config>>,
+}
+
+impl SharedConfig {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The config that was loaded on startup
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// The config of right now. It doesn't change when a reload happens while it's used
+    pub fn get(&self) -> Arc<Config> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replaces the config
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new config
+    pub fn replace(&self, config: Config) {
+        *self.current.write().unwrap() = Arc::new(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: (&str, &str) = ("DISCORD_TOKEN", "config-test-token");
+
+    fn values(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Loads a config from the settings of `dolly.toml`, `.env` and the environment
+    fn load(
+        file: &[(&str, &str)],
+        dotenv: &[(&str, &str)],
+        environment: &[(&str, &str)],
+    ) -> Result<Config, Vec<String>> {
+        Config::merge(
+            [values(file), values(dotenv), values(environment)],
+            Vec::new(),
+            &Logger::new("Config"),
+            &["rage"],
+        )
+    }
+
+    #[test]
+    fn later_sources_win() {
+        let config = load(
+            &[
+                TOKEN,
+                ("OLLAMA_MODEL", "file"),
+                ("OLLAMA_URL", "http://file"),
+            ],
+            &[("OLLAMA_MODEL", "dotenv"), ("OLLAMA_URL", "http://dotenv")],
+            &[("OLLAMA_URL", "http://environment")],
+        )
+        .unwrap();
+
+        assert_eq!(config.discord_token, "config-test-token");
+        assert_eq!(config.ollama.model, "dotenv");
+        assert_eq!(config.ollama.url, "http://environment");
+    }
+
+    #[test]
+    fn flattens_tables_and_lists() {
+        let values = Config::parse_toml(
+            "responds_to = [\"dolly\", \"re:a{1,3}\"]\n\
+             shards = 2\n\
+             [ollama]\n\
+             url = \"http://localhost:11434\"\n\
+             [rage.allowed]\n\
+             users = [1, 2]\n",
+        )
+        .unwrap();
+
+        assert_eq!(values["RESPONDS_TO"], "dolly,re:a{1\\,3}");
+        assert_eq!(values["SHARDS"], "2");
+        assert_eq!(values["OLLAMA_URL"], "http://localhost:11434");
+        assert_eq!(values["RAGE_ALLOWED_USERS"], "1,2");
+    }
+
+    #[test]
+    fn rejects_nested_lists() {
+        assert!(Config::parse_toml("responds_to = [[\"dolly\"]]").is_err());
+        assert!(Config::parse_toml("[[ollama]]\nurl = \"http://localhost\"").is_err());
+        assert!(Config::parse_toml("url = ").is_err());
+    }
+
+    #[test]
+    fn collects_every_invalid_setting() {
+        let errors = load(
+            &[("SHUTDOWN_TIMEOUT", "soon")],
+            &[("LISTEN_ADDR", "everywhere")],
+            &[("GUILD_IDS", "1,two"), ("PRIORTIZE_OLLAMA", "maybe")],
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(errors.len(), 5);
+        assert!(
+            errors
+                .iter()
+                .any(|error| error.starts_with("DISCORD_TOKEN"))
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|error| error.starts_with("SHUTDOWN_TIMEOUT"))
+        );
+        assert!(errors.iter().any(|error| error.starts_with("LISTEN_ADDR")));
+        assert!(errors.iter().any(|error| error.starts_with("GUILD_IDS")));
+        assert!(
+            errors
+                .iter()
+                .any(|error| error.starts_with("PRIORTIZE_OLLAMA"))
+        );
+    }
+
+    #[test]
+    fn only_keeps_known_settings() {
+        let config = load(
+            &[],
+            &[TOKEN],
+            &[
+                ("HOME", "/root"),
+                ("RAGE_ALLOWED_ROLES", "1"),
+                ("MAX_STORED_MESSAGES", "8"),
+            ],
+        )
+        .unwrap();
+
+        assert!(config.values.contains_key("RAGE_ALLOWED_ROLES"));
+        assert!(config.values.contains_key("MAX_STORED_MESSAGES"));
+        assert!(!config.values.contains_key("HOME"));
+    }
+
+    #[test]
+    fn describes_changes_without_secrets() {
+        let old = load(&[], &[TOKEN, ("OPENAI_TOKEN", "old-openai")], &[]).unwrap();
+        let new = load(
+            &[],
+            &[
+                TOKEN,
+                ("MAX_STORED_MESSAGES", "8"),
+                ("OPENAI_TOKEN", "new-openai"),
+            ],
+            &[("HOME", "/root")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            old.changes(&new),
+            [
+                "OPENAI_TOKEN changed",
+                "MAX_STORED_MESSAGES was added as '8'",
+            ]
+        );
+        assert_eq!(
+            new.changes(&old),
+            ["MAX_STORED_MESSAGES was removed", "OPENAI_TOKEN changed"]
+        );
+    }
+
+    #[test]
+    fn lists_the_changes_that_need_a_restart() {
+        let old = load(&[], &[TOKEN, ("GUILD_IDS", "1")], &[]).unwrap();
+        let new = load(
+            &[],
+            &[
+                TOKEN,
+                ("GUILD_IDS", "1,2"),
+                ("RAGE_DISABLED_GUILDS", "3"),
+                ("RAGE_ALLOWED_USERS", "4"),
+                ("MAX_STORED_MESSAGES", "8"),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            old.restart_required(&new),
+            ["GUILD_IDS", "RAGE_DISABLED_GUILDS"]
+        );
+        assert!(old.restart_required(&old).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_startup_settings_until_a_restart() {
+        let reloaded = || {
+            load(
+                &[],
+                &[
+                    TOKEN,
+                    ("GUILD_IDS", "1,2"),
+                    ("RAGE_DISABLED_GUILDS", "3"),
+                    ("MAX_STORED_MESSAGES", "8"),
+                ],
+                &[],
+            )
+            .unwrap()
+        };
+        let current = load(&[], &[TOKEN, ("GUILD_IDS", "1")], &[]).unwrap();
+
+        let mut new = reloaded();
+        new.keep_startup_settings(&current);
+
+        assert_eq!(new.guild_ids, [GuildId::new(1)]);
+        assert!(new.command_rules["rage"].disabled_guilds.is_empty());
+        assert_eq!(new.max_stored_messages, 8);
+        assert_eq!(
+            new.restart_required(&reloaded()),
+            ["GUILD_IDS", "RAGE_DISABLED_GUILDS"]
+        );
+        assert_eq!(new.changes(&reloaded()).len(), 2);
+    }
+}
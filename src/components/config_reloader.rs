@@ -0,0 +1,105 @@
+use super::{CommandPermissions, Config, Logger, Severity, SharedConfig};
+use crate::messages::AIDolly;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Loads the config again while running and hands it to everything that uses it
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `environment` - The environment from before `.env` was applied to it
+/// * `command_names` - The names of the slash commands, used for their permission settings
+/// * `config` - The config that is shared with the commands
+/// * `ai_dolly` - The AI message handler with the providers, triggers and limits
+/// * `command_permissions` - Who can use which slash command
+pub struct ConfigReloader {
+    logger: Logger,
+    environment: HashMap<String, String>,
+    command_names: Vec<&'static str>,
+    config: Arc<SharedConfig>,
+    ai_dolly: Arc<AIDolly>,
+    command_permissions: Arc<CommandPermissions>,
+}
+
+impl ConfigReloader {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `environment` - The environment from before `.env` was applied to it
+    /// * `command_names` - The names of the slash commands
+    /// * `config` - The config that is shared with the commands
+    /// * `ai_dolly` - The AI message handler
+    /// * `command_permissions` - Who can use which slash command
+    pub fn new(
+        environment: HashMap<String, String>,
+        command_names: Vec<&'static str>,
+        config: Arc<SharedConfig>,
+        ai_dolly: Arc<AIDolly>,
+        command_permissions: Arc<CommandPermissions>,
+    ) -> Self {
+        Self {
+            logger: Logger::new("ConfigReloader"),
+            environment,
+            command_names,
+            config,
+            ai_dolly,
+            command_permissions,
+        }
+    }
+
+    /// Loads `dolly.toml`, `.env` and the environment again and logs what changed. Nothing is
+    /// applied when a setting is invalid, so the bot never runs with half a config
+    pub fn reload(&self) {
+        self.logger.info("Reloading the config");
+
+        let mut new = match Config::load(&self.environment, &self.command_names) {
+            Ok(config) => config,
+            Err(errors) => {
+                for error in &errors {
+                    self.logger.error(error, Severity::High);
+                }
+                self.logger.error(
+                    format!(
+                        "Found {} invalid settings, keeping the current config",
+                        errors.len()
+                    )
+                    .as_str(),
+                    Severity::High,
+                );
+                return;
+            }
+        };
+
+        let current = self.config.get();
+        let changes = current.changes(&new);
+
+        if changes.is_empty() {
+            self.logger.info("The config didn't change");
+            return;
+        }
+
+        for change in &changes {
+            self.logger.info(change);
+        }
+
+        let restart_required = current.restart_required(&new);
+        if !restart_required.is_empty() {
+            self.logger.warning(
+                format!("Restart the bot to apply {}", restart_required.join(", ")).as_str(),
+                Severity::Medium,
+            );
+            new.keep_startup_settings(&current);
+        }
+
+        self.ai_dolly.reload(&new);
+        self.command_permissions.reload(new.command_rules.clone());
+        self.config.replace(new);
+
+        self.logger.info(format!(
+            "Reloaded the config with {} changes",
+            changes.len()
+        ));
+    }
+}
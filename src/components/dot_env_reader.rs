@@ -1,16 +1,29 @@
-use rust_logger::Logger;
+use super::{Logger, Severity, redact};
 use std::{collections::HashMap, env, fs::read_to_string};
 pub struct DotEnvReader {
     logger: Logger,
     file_name: String,
+    environment: HashMap<String, String>,
 }
 
 impl DotEnvReader {
-    pub fn new<S: AsRef<str>>(file_name: S) -> Self {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The file to read. Like `.env`
+    /// * `environment` - The environment from before `.env` was applied to it, used for
+    ///   expanding variables. The process environment can't be used, because it still has the
+    ///   values of `.env` from the start after the file changed
+    pub fn new<S: AsRef<str>>(file_name: S, environment: &HashMap<String, String>) -> Self {
         let logger = Logger::new("DotEnvReader");
         let file_name = file_name.as_ref().to_string();
 
-        Self { logger, file_name }
+        Self {
+            logger,
+            file_name,
+            environment: environment.clone(),
+        }
     }
 
     pub fn parse_and_set_env(&self) {
@@ -28,6 +41,11 @@ impl DotEnvReader {
         self.logger.debug("Set enviroment variables")
     }
 
+    /// Reads the variables of the file without setting them in the environment
+    pub fn read(&self) -> HashMap<String, String> {
+        self.parse_lines(self.read_file_content())
+    }
+
     fn read_file_content(&self) -> String {
         match read_to_string(&self.file_name) {
             Ok(content) => content,
@@ -39,36 +57,267 @@ impl DotEnvReader {
         }
     }
 
+    /// Parses the dotenv grammar:
+    ///
+    /// * `KEY=value` and `export KEY=value`
+    /// * Comments on their own line, or after an unquoted value with a space before the `#`
+    /// * `'single quoted'` values that are used as they are
+    /// * `"double quoted"` values with `\n`, `\t`, `\r`, `\"`, `\\` and `\$` escapes
+    /// * Quoted values that span multiple lines. A quote that is never closed only skips its own
+    ///   line, so the settings after it are still read
+    /// * `${VAR}`, `${VAR:-default}` and `$VAR` in unquoted and double quoted values
     fn parse_lines(&self, content: String) -> HashMap<String, String> {
         let mut env_vars = HashMap::new();
-        for line in content.lines() {
-            let split_line: Vec<&str> = line.split("#").collect();
-            let trimmed_line = split_line[0].trim();
+        let lines: Vec<&str> = content.lines().collect();
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line_number = index + 1;
+            let line = lines[index].trim();
+            index += 1;
 
             // Skip empty lines and comments
-            if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line = line
+                .strip_prefix("export ")
+                .map(str::trim_start)
+                .unwrap_or(line);
+
+            let Some((key, value)) = line.split_once('=') else {
+                self.warn(line_number, "expected KEY=value");
+                continue;
+            };
+
+            let key = key.trim();
+            if !Self::is_valid_key(key) {
+                self.warn(
+                    line_number,
+                    &format!(
+                        "'{}' is not a valid name. Use letters, numbers and underscores",
+                        key
+                    ),
+                );
                 continue;
             }
 
-            if let Some((key, value)) = self.parse_key_value(trimmed_line) {
-                if value.is_empty() {
-                    continue;
+            let value = value.trim_start();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let mut quoted = value[1..].to_string();
+                    let mut next = index;
+
+                    // Quoted values can continue on the next lines
+                    let end = loop {
+                        if let Some(end) = Self::closing_quote(&quoted, quote) {
+                            break Some(end);
+                        }
+
+                        match lines.get(next) {
+                            Some(next_line) => {
+                                quoted.push('\n');
+                                quoted.push_str(next_line);
+                                next += 1;
+                            }
+                            None => break None,
+                        }
+                    };
+
+                    // Parsing goes on from the next line, so a typo doesn't swallow the rest
+                    let Some(end) = end else {
+                        self.warn(
+                            line_number,
+                            &format!("the value of {} is missing a closing {}", key, quote),
+                        );
+                        continue;
+                    };
+                    index = next;
+
+                    let rest = quoted[end + 1..].trim();
+                    if !rest.is_empty() && !rest.starts_with('#') {
+                        self.warn(
+                            line_number,
+                            &format!("ignoring '{}' after the closing {}", rest, quote),
+                        );
+                    }
+
+                    quoted.truncate(end);
+
+                    if quote == '"' {
+                        self.interpolate(&quoted, true, &env_vars)
+                    } else {
+                        quoted
+                    }
+                }
+                _ => {
+                    // A # only starts a comment at the start of the value or after whitespace, so
+                    // tokens and urls can have one
+                    let value = match value.starts_with('#') {
+                        true => "",
+                        false => match value.find(" #").or_else(|| value.find("\t#")) {
+                            Some(comment) => &value[..comment],
+                            None => value,
+                        },
+                    };
+                    self.interpolate(value.trim(), false, &env_vars)
                 }
+            };
 
-                self.logger
-                    .debug(format!("Parsed key-value pair: {}={}", key, value).as_str());
-                env_vars.insert(key, value);
+            if value.is_empty() {
+                continue;
             }
+
+            self.logger
+                .debug(format!("Parsed key-value pair: {}={}", key, redact(key, &value)).as_str());
+            env_vars.insert(key.to_string(), value);
         }
 
         env_vars
     }
 
-    fn parse_key_value(&self, line: &str) -> Option<(String, String)> {
-        let mut split = line.splitn(2, '=');
-        let key = split.next()?;
-        let value = split.next().unwrap_or("").trim().to_string();
-        Some((key.trim().to_string(), value))
+    fn warn(&self, line_number: usize, reason: &str) {
+        self.logger.warning(
+            format!(
+                "Skipping line {} of {}: {}",
+                line_number, self.file_name, reason
+            )
+            .as_str(),
+            Severity::Medium,
+        );
+    }
+
+    fn is_valid_key(key: &str) -> bool {
+        let mut chars = key.chars();
+
+        chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+    }
+
+    /// Finds the quote that ends the value. In double quotes a `\"` doesn't end it
+    fn closing_quote(value: &str, quote: char) -> Option<usize> {
+        let mut escaped = false;
+
+        for (index, char) in value.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if char == '\\' && quote == '"' {
+                escaped = true;
+            } else if char == quote {
+                return Some(index);
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the escape sequences and variables in a value
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value without quotes
+    /// * `escapes` - If escape sequences like `\n` are replaced
+    /// * `env_vars` - The variables that have been parsed so far
+    fn interpolate(
+        &self,
+        value: &str,
+        escapes: bool,
+        env_vars: &HashMap<String, String>,
+    ) -> String {
+        let mut result = String::with_capacity(value.len());
+        let mut chars = value.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' if escapes => match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some('t') => result.push('\t'),
+                    Some('r') => result.push('\r'),
+                    Some(other @ ('"' | '\\' | '$')) => result.push(other),
+                    Some(other) => {
+                        result.push('\\');
+                        result.push(other);
+                    }
+                    None => result.push('\\'),
+                },
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    let mut expression = String::new();
+                    let mut closed = false;
+
+                    for char in chars.by_ref() {
+                        if char == '}' {
+                            closed = true;
+                            break;
+                        }
+                        expression.push(char);
+                    }
+
+                    if !closed {
+                        // Not a variable, so keep it as it is
+                        result.push_str("${");
+                        result.push_str(&expression);
+                        continue;
+                    }
+
+                    let (name, default) = match expression.split_once(":-") {
+                        Some((name, default)) => (name, Some(default)),
+                        None => (expression.as_str(), None),
+                    };
+
+                    match (self.lookup(name, env_vars), default) {
+                        (Some(found), _) => result.push_str(&found),
+                        (None, Some(default)) => result.push_str(default),
+                        (None, None) => {}
+                    }
+                }
+                '$' if chars
+                    .peek()
+                    .is_some_and(|next| next.is_ascii_alphabetic() || *next == '_') =>
+                {
+                    let mut name = String::new();
+
+                    while let Some(next) = chars.peek() {
+                        if !next.is_ascii_alphanumeric() && *next != '_' {
+                            break;
+                        }
+                        name.push(*next);
+                        chars.next();
+                    }
+
+                    result.push_str(&self.lookup(&name, env_vars).unwrap_or_default());
+                }
+                _ => result.push(char),
+            }
+        }
+
+        result
+    }
+
+    /// The value of a variable. The environment wins from the file, like it does for the
+    /// variables themselves
+    fn lookup(&self, name: &str, env_vars: &HashMap<String, String>) -> Option<String> {
+        let value = self
+            .environment
+            .get(name)
+            .or_else(|| env_vars.get(name))
+            .cloned()
+            .filter(|value| !value.is_empty());
+
+        if value.is_none() {
+            self.logger.debug(
+                format!(
+                    "{} is used in {} but has not been set",
+                    name, self.file_name
+                )
+                .as_str(),
+            );
+        }
+
+        value
     }
 
     fn set_env_vars(&self, env_vars: HashMap<String, String>) {
@@ -79,8 +328,14 @@ impl DotEnvReader {
                     format!("Environment variable {} is already set, skipping.", key).as_str(),
                 );
             } else {
-                self.logger
-                    .debug(format!("Setting environment variable: {}={}", key, value).as_str());
+                self.logger.debug(
+                    format!(
+                        "Setting environment variable: {}={}",
+                        key,
+                        redact(&key, &value)
+                    )
+                    .as_str(),
+                );
                 unsafe {
                     env::set_var(&key, &value);
                 }
@@ -88,3 +343,188 @@ impl DotEnvReader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> HashMap<String, String> {
+        DotEnvReader::new(".env.test", &HashMap::new()).parse_lines(content.to_string())
+    }
+
+    fn get(vars: &HashMap<String, String>, key: &str) -> Option<String> {
+        vars.get(key).cloned()
+    }
+
+    #[test]
+    fn parses_plain_values() {
+        let vars = parse("DISCORD_TOKEN=abc\n  OLLAMA_MODEL = llama3.1  \n");
+
+        assert_eq!(get(&vars, "DISCORD_TOKEN").as_deref(), Some("abc"));
+        assert_eq!(get(&vars, "OLLAMA_MODEL").as_deref(), Some("llama3.1"));
+    }
+
+    #[test]
+    fn skips_comments_and_empty_lines() {
+        let vars = parse("# A comment\n\n   # Indented comment\nKEY=value\n");
+
+        assert_eq!(vars.len(), 1);
+        assert_eq!(get(&vars, "KEY").as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn strips_inline_comments_after_whitespace() {
+        let vars =
+            parse("GUILD_ID=84343943     # ID of your Discord server\nTAB=value\t# comment\n");
+
+        assert_eq!(get(&vars, "GUILD_ID").as_deref(), Some("84343943"));
+        assert_eq!(get(&vars, "TAB").as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn keeps_hashes_inside_values() {
+        let vars = parse("TOKEN=abc#def\nURL=http://localhost:11434/#anchor\n");
+
+        assert_eq!(get(&vars, "TOKEN").as_deref(), Some("abc#def"));
+        assert_eq!(
+            get(&vars, "URL").as_deref(),
+            Some("http://localhost:11434/#anchor")
+        );
+    }
+
+    #[test]
+    fn supports_export() {
+        let vars = parse("export OLLAMA_URL=http://localhost:11434\n");
+
+        assert_eq!(
+            get(&vars, "OLLAMA_URL").as_deref(),
+            Some("http://localhost:11434")
+        );
+    }
+
+    #[test]
+    fn double_quoted_values_keep_hashes_and_spaces() {
+        let vars = parse("PROMPT=\"  hello # world  \" # comment\n");
+
+        assert_eq!(get(&vars, "PROMPT").as_deref(), Some("  hello # world  "));
+    }
+
+    #[test]
+    fn double_quoted_values_support_escapes() {
+        let vars = parse(r#"VALUE="line\nnext\ttab \"quoted\" back\\slash \$HOME""#);
+
+        assert_eq!(
+            get(&vars, "VALUE").as_deref(),
+            Some("line\nnext\ttab \"quoted\" back\\slash $HOME")
+        );
+    }
+
+    #[test]
+    fn single_quoted_values_are_literal() {
+        let vars = parse(r"VALUE='no\nescape ${NOT_EXPANDED} # not a comment'");
+
+        assert_eq!(
+            get(&vars, "VALUE").as_deref(),
+            Some(r"no\nescape ${NOT_EXPANDED} # not a comment")
+        );
+    }
+
+    #[test]
+    fn quoted_values_can_span_multiple_lines() {
+        let vars =
+            parse("CERT=\"-----BEGIN-----\nabc\n-----END-----\"\nNEXT='one\ntwo'\nAFTER=yes\n");
+
+        assert_eq!(
+            get(&vars, "CERT").as_deref(),
+            Some("-----BEGIN-----\nabc\n-----END-----")
+        );
+        assert_eq!(get(&vars, "NEXT").as_deref(), Some("one\ntwo"));
+        assert_eq!(get(&vars, "AFTER").as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn expands_variables_from_earlier_lines() {
+        let vars = parse(
+            "DOTENV_TEST_HOST=localhost\nURL=http://${DOTENV_TEST_HOST}:11434\nQUOTED=\"$DOTENV_TEST_HOST/api\"\n",
+        );
+
+        assert_eq!(get(&vars, "URL").as_deref(), Some("http://localhost:11434"));
+        assert_eq!(get(&vars, "QUOTED").as_deref(), Some("localhost/api"));
+    }
+
+    #[test]
+    fn expands_variables_from_the_environment_snapshot() {
+        let environment = HashMap::from([("DOTENV_TEST_HOST".to_string(), "real".to_string())]);
+        let vars = DotEnvReader::new(".env.test", &environment).parse_lines(
+            "DOTENV_TEST_HOST=file\nURL=http://${DOTENV_TEST_HOST}\nPATH_TEST=$PATH\n".to_string(),
+        );
+
+        // The environment wins from the file, but the rest of the process environment is unused
+        assert_eq!(get(&vars, "URL").as_deref(), Some("http://real"));
+        assert_eq!(get(&vars, "PATH_TEST"), None);
+    }
+
+    #[test]
+    fn expands_defaults_and_missing_variables() {
+        let vars = parse(
+            "WITH_DEFAULT=${DOTENV_TEST_MISSING:-fallback}\nMISSING=a${DOTENV_TEST_MISSING}b\n",
+        );
+
+        assert_eq!(get(&vars, "WITH_DEFAULT").as_deref(), Some("fallback"));
+        assert_eq!(get(&vars, "MISSING").as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn keeps_dollar_signs_that_are_not_variables() {
+        let vars = parse("PRICE=5$\nUNCLOSED=${OOPS\n");
+
+        assert_eq!(get(&vars, "PRICE").as_deref(), Some("5$"));
+        assert_eq!(get(&vars, "UNCLOSED").as_deref(), Some("${OOPS"));
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let vars = parse(
+            "NO_EQUALS_SIGN\n1INVALID=value\nBAD KEY=value\nUNCLOSED=\"never closed\nVALID=yes\n",
+        );
+
+        assert_eq!(vars.len(), 1);
+        assert_eq!(get(&vars, "VALID").as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn keeps_parsing_after_a_malformed_line() {
+        let vars = parse("NO_EQUALS_SIGN\nVALID=yes\n");
+
+        assert_eq!(vars.len(), 1);
+        assert_eq!(get(&vars, "VALID").as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn skips_empty_values() {
+        let vars = parse("OPENAI_TOKEN=\nCOHERE_TOKEN=\"\"\n");
+
+        assert!(vars.is_empty());
+    }
+
+    #[test]
+    fn skips_values_that_are_only_a_comment() {
+        let vars = parse(
+            "LISTEN_ADDR=                     # Like 0.0.0.0:9090\n\
+             OPENAI_TOKEN=\t# Leave empty to not use OpenAI\n\
+             SHARDS=#auto\n\
+             GUILD_IDS=84343943              # Comma-separated ids\n",
+        );
+
+        assert_eq!(vars.len(), 1);
+        assert_eq!(get(&vars, "GUILD_IDS").as_deref(), Some("84343943"));
+    }
+
+    #[test]
+    fn rejects_keys_with_other_characters() {
+        let vars = parse("OLLAMA.URL=http://localhost\nOLLAMA-MODEL=llama3.2\nNUM_CTX=2048\n");
+
+        assert_eq!(vars.len(), 1);
+        assert_eq!(get(&vars, "NUM_CTX").as_deref(), Some("2048"));
+    }
+}
@@ -0,0 +1,164 @@
+use super::{ConfigReader, Logger, Severity};
+use reqwest::{Certificate, Client, Proxy, Url};
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+
+/// The settings of the HTTP client
+///
+/// # Fields
+///
+/// * `user_agent` - The user agent of every request from `HTTP_USER_AGENT`
+/// * `proxy` - The proxy every request goes through from `HTTP_PROXY_URL`
+/// * `certificates` - Extra trusted certificates from the PEM file in `HTTP_CA_BUNDLE`
+/// * `timeout` - The timeout of a request from `HTTP_TIMEOUT`
+/// * `host_timeouts` - The timeouts per host from `HTTP_HOST_TIMEOUTS`
+#[derive(Clone)]
+pub struct HttpConfig {
+    pub user_agent: String,
+    pub proxy: Option<Proxy>,
+    pub certificates: Vec<Certificate>,
+    pub timeout: Duration,
+    pub host_timeouts: HashMap<String, Duration>,
+}
+
+impl HttpConfig {
+    /// Reads the settings from the config. The CA bundle is read right away, so a missing or
+    /// invalid file is reported on startup
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let user_agent = reader.string(
+            "HTTP_USER_AGENT",
+            &format!("dolly_parton/{}", env!("CARGO_PKG_VERSION")),
+        );
+
+        let proxy = reader.parse("HTTP_PROXY_URL", "a url like http://proxy:3128", |value| {
+            Proxy::all(value).ok()
+        });
+
+        let mut certificates = Vec::new();
+        if let Some(ca_bundle) = reader.optional("HTTP_CA_BUNDLE") {
+            match fs::read(&ca_bundle).map(|pem| Certificate::from_pem_bundle(&pem)) {
+                Ok(Ok(bundle)) => certificates = bundle,
+                Ok(Err(why)) => reader.error(format!(
+                    "HTTP_CA_BUNDLE '{}' is not valid PEM: {}",
+                    ca_bundle, why
+                )),
+                Err(why) => reader.error(format!(
+                    "Could not read HTTP_CA_BUNDLE '{}': {}",
+                    ca_bundle, why
+                )),
+            }
+        }
+
+        let host_timeouts = reader
+            .list(
+                "HTTP_HOST_TIMEOUTS",
+                "host=seconds like api.openai.com=60",
+                |entry| {
+                    let (host, seconds) = entry.split_once('=')?;
+                    let seconds = seconds.trim().parse().ok()?;
+                    Some((host.trim().to_lowercase(), Duration::from_secs(seconds)))
+                },
+            )
+            .into_iter()
+            .collect();
+
+        Self {
+            user_agent,
+            proxy,
+            certificates,
+            timeout: Duration::from_secs(reader.number("HTTP_TIMEOUT", 30)),
+            host_timeouts,
+        }
+    }
+}
+
+/// The HTTP client that is shared by the AI providers and commands, so connections and TLS
+/// sessions get reused
+///
+/// # Fields
+///
+/// * `client` - The configured reqwest client
+/// * `default_timeout` - The timeout of a request from `HTTP_TIMEOUT`
+/// * `host_timeouts` - The timeouts per host from `HTTP_HOST_TIMEOUTS`
+pub struct HttpClient {
+    client: Client,
+    default_timeout: Duration,
+    host_timeouts: HashMap<String, Duration>,
+}
+
+impl HttpClient {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The proxy, certificates, user agent and timeouts from the config
+    pub fn new(config: &HttpConfig) -> Self {
+        let logger = Logger::new("HttpClient");
+
+        let mut builder = Client::builder()
+            .user_agent(config.user_agent.clone())
+            .connect_timeout(Duration::from_secs(10));
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+
+        for certificate in &config.certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+
+        let client = builder.build().unwrap_or_else(|why| {
+            logger.error(
+                format!(
+                    "Could not create the HTTP client: {}. Using the default one",
+                    why
+                )
+                .as_str(),
+                Severity::High,
+            );
+            Client::new()
+        });
+
+        Self {
+            client,
+            default_timeout: config.timeout,
+            host_timeouts: config.host_timeouts.clone(),
+        }
+    }
+
+    /// The shared reqwest client
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The timeout of a request to a url. A timeout from `HTTP_HOST_TIMEOUTS` wins
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url of the request
+    /// * `default` - The timeout when the host has no timeout of its own
+    pub fn timeout_for(&self, url: &str, default: Duration) -> Duration {
+        Url::parse(url)
+            .ok()
+            .and_then(|url| {
+                url.host_str()
+                    .and_then(|host| self.host_timeouts.get(&host.to_lowercase()).copied())
+            })
+            .unwrap_or(default)
+    }
+
+    /// The timeout of a request to a url, using `HTTP_TIMEOUT` when the host has no timeout
+    /// of its own
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url of the request
+    pub fn timeout(&self, url: &str) -> Duration {
+        self.timeout_for(url, self.default_timeout)
+    }
+}
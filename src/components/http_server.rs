@@ -0,0 +1,188 @@
+use super::{Logger, Severity};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests with a bigger header than this are refused
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// A response of the HTTP server
+///
+/// # Fields
+///
+/// * `status` - The status code. Like `200`
+/// * `content_type` - The type of the body. Like `text/plain`
+/// * `body` - The body of the response
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// A plain text response
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status code
+    /// * `body` - The text of the response
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            503 => "Service Unavailable",
+            _ => "",
+        }
+    }
+}
+
+type Route = Box<dyn Fn() -> HttpResponse + Send + Sync>;
+
+/// A tiny HTTP server for metrics and health checks. It only supports `GET` and `HEAD`
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `routes` - The handlers per path
+pub struct HttpServer {
+    logger: Logger,
+    routes: HashMap<&'static str, Route>,
+}
+
+impl HttpServer {
+    /// Constructor
+    pub fn new() -> Self {
+        Self {
+            logger: Logger::new("HttpServer"),
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Adds a handler for a path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path like `/metrics`
+    /// * `handler` - Creates the response
+    pub fn route<F>(mut self, path: &'static str, handler: F) -> Self
+    where
+        F: Fn() -> HttpResponse + Send + Sync + 'static,
+    {
+        self.routes.insert(path, Box::new(handler));
+        self
+    }
+
+    /// Starts listening in the background
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to listen on. Like `0.0.0.0:9090`
+    pub fn spawn(self, address: String) {
+        let server = Arc::new(self);
+
+        tokio::spawn(async move {
+            let listener = match TcpListener::bind(&address).await {
+                Ok(listener) => listener,
+                Err(why) => {
+                    server.logger.error(
+                        format!("Could not listen on {}: {}", address, why).as_str(),
+                        Severity::High,
+                    );
+                    return;
+                }
+            };
+
+            server
+                .logger
+                .info(format!("Listening on http://{}", address));
+
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let server = server.clone();
+                        tokio::spawn(async move { server.handle(stream).await });
+                    }
+                    Err(why) => server.logger.warning(
+                        format!("Could not accept connection: {}", why).as_str(),
+                        Severity::Low,
+                    ),
+                }
+            }
+        });
+    }
+
+    async fn handle(&self, mut stream: TcpStream) {
+        let request =
+            match tokio::time::timeout(Duration::from_secs(5), Self::read_request(&mut stream))
+                .await
+            {
+                Ok(Some(request)) => request,
+                _ => return,
+            };
+
+        // Only the request line matters, like `GET /metrics HTTP/1.1`
+        let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+
+        let response = match (method, self.routes.get(path)) {
+            ("GET" | "HEAD", Some(handler)) => handler(),
+            ("GET" | "HEAD", None) => HttpResponse::text(404, "Not found\n"),
+            ("", _) => HttpResponse::text(400, "Bad request\n"),
+            _ => HttpResponse::text(405, "Method not allowed\n"),
+        };
+
+        let body = if method == "HEAD" { "" } else { &response.body };
+        let raw = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            response.reason(),
+            response.content_type,
+            response.body.len(),
+            body
+        );
+
+        if let Err(why) = stream.write_all(raw.as_bytes()).await {
+            self.logger
+                .debug(format!("Could not send response: {}", why).as_str());
+        }
+        let _ = stream.shutdown().await;
+    }
+
+    /// Reads until the end of the headers
+    async fn read_request(stream: &mut TcpStream) -> Option<String> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 1024];
+
+        loop {
+            let read = stream.read(&mut chunk).await.ok()?;
+            if read == 0 {
+                break;
+            }
+
+            buffer.extend_from_slice(&chunk[..read]);
+
+            if buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+                break;
+            }
+
+            if buffer.len() > MAX_REQUEST_SIZE {
+                return None;
+            }
+        }
+
+        Some(String::from_utf8_lossy(&buffer).to_string())
+    }
+}
@@ -0,0 +1,21 @@
+/// Parses a comma separated list of Discord ids
+///
+/// # Arguments
+///
+/// * `value` - The comma separated ids
+///
+/// # Returns
+///
+/// The first invalid id as an error
+pub fn parse_id_list(value: &str) -> Result<Vec<u64>, String> {
+    let mut ids = Vec::new();
+
+    for id in value.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        match id.parse::<u64>() {
+            Ok(parsed) if parsed != 0 => ids.push(parsed),
+            _ => return Err(id.to_string()),
+        }
+    }
+
+    Ok(ids)
+}
@@ -0,0 +1,44 @@
+use super::redact_secrets;
+pub use rust_logger::Severity;
+
+/// Logs like `rust_logger::Logger`, but removes tokens and the passwords of urls from every
+/// message first
+///
+/// # Fields
+///
+/// * `inner` - The logger that writes the messages
+#[derive(Clone)]
+pub struct Logger {
+    inner: rust_logger::Logger,
+}
+
+impl Logger {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `type_name` - The name that's shown in front of every message. Like `AIdolly`
+    pub fn new<S: AsRef<str>>(type_name: S) -> Self {
+        Self {
+            inner: rust_logger::Logger::new(type_name),
+        }
+    }
+
+    pub fn info<S: AsRef<str>>(&self, message: S) {
+        self.inner.info(redact_secrets(message.as_ref()));
+    }
+
+    /// Only shown when `DEBUG` is `true`
+    pub fn debug<S: AsRef<str>>(&self, message: S) {
+        self.inner.debug(redact_secrets(message.as_ref()));
+    }
+
+    pub fn warning<S: AsRef<str>>(&self, message: S, severity: Severity) {
+        self.inner
+            .warning(redact_secrets(message.as_ref()), severity);
+    }
+
+    pub fn error<S: AsRef<str>>(&self, message: S, severity: Severity) {
+        self.inner.error(redact_secrets(message.as_ref()), severity);
+    }
+}
@@ -0,0 +1,158 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+/// The Prometheus metrics of the bot
+///
+/// # Fields
+///
+/// * `registry` - The registry that contains every metric
+/// * `messages_received` - The amount of messages the bot has received per shard
+/// * `handler_matches` - The amount of messages handled per message handler
+/// * `llm_requests` - The amount of AI requests per provider and outcome
+/// * `llm_latency` - How long the AI requests take per provider
+/// * `llm_tokens` - The tokens used per provider and kind
+/// * `slash_commands` - The amount of slash commands used per name
+/// * `send_errors` - The amount of messages or interaction responses that could not be sent
+/// * `rate_limited` - The amount of messages and commands that were rate limited per shard
+/// * `shard_connected` - If the gateway of a shard is connected
+pub struct Metrics {
+    registry: Registry,
+    pub messages_received: IntCounterVec,
+    pub handler_matches: IntCounterVec,
+    pub llm_requests: IntCounterVec,
+    pub llm_latency: HistogramVec,
+    pub llm_tokens: IntCounterVec,
+    pub slash_commands: IntCounterVec,
+    pub send_errors: IntCounterVec,
+    pub rate_limited: IntCounterVec,
+    pub shard_connected: IntGaugeVec,
+}
+
+impl Metrics {
+    /// Constructor that registers every metric
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("dolly".to_string()), None).unwrap();
+
+        let messages_received = IntCounterVec::new(
+            Opts::new(
+                "messages_received_total",
+                "Messages received from Discord per shard",
+            ),
+            &["shard"],
+        )
+        .unwrap();
+
+        let handler_matches = IntCounterVec::new(
+            Opts::new(
+                "handler_matches_total",
+                "Messages that were handled per message handler",
+            ),
+            &["handler"],
+        )
+        .unwrap();
+
+        let llm_requests = IntCounterVec::new(
+            Opts::new(
+                "llm_requests_total",
+                "AI requests per provider and outcome (success, failure, cached or skipped)",
+            ),
+            &["provider", "outcome"],
+        )
+        .unwrap();
+
+        let llm_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "llm_request_duration_seconds",
+                "How long AI requests take per provider",
+            )
+            .buckets(vec![
+                0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0,
+            ]),
+            &["provider"],
+        )
+        .unwrap();
+
+        let llm_tokens = IntCounterVec::new(
+            Opts::new(
+                "llm_tokens_total",
+                "Tokens used per provider and kind (input or output)",
+            ),
+            &["provider", "kind"],
+        )
+        .unwrap();
+
+        let slash_commands = IntCounterVec::new(
+            Opts::new("slash_commands_total", "Slash commands used per name"),
+            &["command"],
+        )
+        .unwrap();
+
+        let send_errors = IntCounterVec::new(
+            Opts::new(
+                "discord_send_errors_total",
+                "Messages (message) or command responses (interaction) that could not be sent",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "rate_limited_total",
+                "Messages and commands that were rate limited per shard",
+            ),
+            &["shard"],
+        )
+        .unwrap();
+
+        let shard_connected = IntGaugeVec::new(
+            Opts::new(
+                "shard_connected",
+                "If the gateway of a shard is connected (1) or not (0)",
+            ),
+            &["shard"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(messages_received.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(handler_matches.clone()))
+            .unwrap();
+        registry.register(Box::new(llm_requests.clone())).unwrap();
+        registry.register(Box::new(llm_latency.clone())).unwrap();
+        registry.register(Box::new(llm_tokens.clone())).unwrap();
+        registry.register(Box::new(slash_commands.clone())).unwrap();
+        registry.register(Box::new(send_errors.clone())).unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+        registry
+            .register(Box::new(shard_connected.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            messages_received,
+            handler_matches,
+            llm_requests,
+            llm_latency,
+            llm_tokens,
+            slash_commands,
+            send_errors,
+            rate_limited,
+            shard_connected,
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+
+        if let Err(why) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return format!("# Could not encode metrics: {}\n", why);
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
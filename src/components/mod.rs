@@ -1,3 +1,37 @@
 // Files
+pub mod atomic_file;
+pub mod circuit_breaker;
+pub mod command_permissions;
+pub mod config;
+pub mod config_reloader;
 pub mod dot_env_reader;
+pub mod http_client;
+pub mod http_server;
+pub mod id_list;
+pub mod logger;
+pub mod metrics;
+pub mod rate_limiter;
+pub mod readiness;
+pub mod retry;
+pub mod secrets;
+pub mod sharding;
+pub mod shared_state;
+pub mod shutdown;
+pub use atomic_file::*;
+pub use circuit_breaker::*;
+pub use command_permissions::*;
+pub use config::*;
+pub use config_reloader::*;
 pub use dot_env_reader::*;
+pub use http_client::*;
+pub use http_server::*;
+pub use id_list::*;
+pub use logger::*;
+pub use metrics::*;
+pub use rate_limiter::*;
+pub use readiness::*;
+pub use retry::*;
+pub use secrets::*;
+pub use sharding::*;
+pub use shared_state::*;
+pub use shutdown::*;
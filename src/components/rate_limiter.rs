@@ -0,0 +1,254 @@
+use super::{ConfigReader, Logger};
+use serenity::model::id::{ChannelId, RoleId, UserId};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// After this many buckets the full ones get removed, so the map doesn't keep growing
+const MAX_BUCKETS: usize = 1000;
+
+/// How many requests are allowed in a period of time. Like `5/60` for 5 requests per minute
+///
+/// # Fields
+///
+/// * `capacity` - The amount of requests that can be made at once
+/// * `period` - The time it takes to refill all the requests
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// Parses a limit like `5/60`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The amount of requests and the period in seconds separated by a `/`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (capacity, seconds) = value.trim().split_once('/')?;
+        let capacity = capacity.trim().parse::<u32>().ok()?;
+        let seconds = seconds.trim().parse::<u64>().ok()?;
+
+        if capacity == 0 || seconds == 0 {
+            return None;
+        }
+
+        Some(Self {
+            capacity: capacity as f64,
+            period: Duration::from_secs(seconds),
+        })
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        self.capacity / self.period.as_secs_f64()
+    }
+}
+
+/// A token bucket that refills over time
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            tokens: limit.capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.refill_per_second()).min(limit.capacity);
+        self.last_refill = now;
+    }
+
+    /// How long it takes until there is a token again
+    fn wait_time(&self, limit: &RateLimit) -> Duration {
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64((1.0 - self.tokens) / limit.refill_per_second())
+    }
+}
+
+/// The limits of the rate limiter
+///
+/// # Fields
+///
+/// * `user_limit` - The limit per user from `RATE_LIMIT_USER`
+/// * `channel_limit` - The limit per channel from `RATE_LIMIT_CHANNEL`
+/// * `global_limit` - The limit for everyone on the same shard from `RATE_LIMIT_GLOBAL`
+/// * `bypass_roles` - Roles from `RATE_LIMIT_BYPASS_ROLES` that are never limited
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiterConfig {
+    pub user_limit: Option<RateLimit>,
+    pub channel_limit: Option<RateLimit>,
+    pub global_limit: Option<RateLimit>,
+    pub bypass_roles: Vec<RoleId>,
+}
+
+impl RateLimiterConfig {
+    /// Reads the limits from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let expected = "a limit like 5/60";
+
+        Self {
+            user_limit: reader.parse("RATE_LIMIT_USER", expected, RateLimit::parse),
+            channel_limit: reader.parse("RATE_LIMIT_CHANNEL", expected, RateLimit::parse),
+            global_limit: reader.parse("RATE_LIMIT_GLOBAL", expected, RateLimit::parse),
+            bypass_roles: reader
+                .id_list("RATE_LIMIT_BYPASS_ROLES")
+                .into_iter()
+                .map(RoleId::new)
+                .collect(),
+        }
+    }
+}
+
+/// Limits how often the AI can be used per user, per channel and in total
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `config` - The limits, which can be replaced while running
+/// * `buckets` - The token buckets per user, channel and the global one
+pub struct RateLimiter {
+    logger: Logger,
+    config: RwLock<RateLimiterConfig>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The limits from the config
+    pub fn new(config: &RateLimiterConfig) -> Self {
+        Self {
+            logger: Logger::new("RateLimiter"),
+            config: RwLock::new(config.clone()),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the limits. The buckets are kept, so nobody gets extra requests from a reload
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new limits
+    pub fn reload(&self, config: &RateLimiterConfig) {
+        *self.config.write().unwrap() = config.clone();
+    }
+
+    /// Takes a token from the user, channel and global bucket if all of them have one left.
+    /// Every shard has its own global bucket, so the shards of other processes don't share it
+    ///
+    /// # Arguments
+    ///
+    /// * `shard_id` - The shard the request came in on
+    /// * `user_id` - The user that wants to use the AI
+    /// * `channel_id` - The channel the AI is used in
+    /// * `roles` - The roles of the user, used for bypassing the limits
+    ///
+    /// # Returns
+    ///
+    /// How long the user has to wait as an error
+    pub fn check(
+        &self,
+        shard_id: u32,
+        user_id: UserId,
+        channel_id: ChannelId,
+        roles: &[RoleId],
+    ) -> Result<(), Duration> {
+        let config = self.config.read().unwrap().clone();
+
+        if roles.iter().any(|role| config.bypass_roles.contains(role)) {
+            return Ok(());
+        }
+
+        let limits: Vec<(String, RateLimit)> = [
+            (format!("global:{}", shard_id), config.global_limit),
+            (format!("channel:{}", channel_id), config.channel_limit),
+            (format!("user:{}", user_id), config.user_limit),
+        ]
+        .into_iter()
+        .filter_map(|(key, limit)| limit.map(|limit| (key, limit)))
+        .collect();
+
+        if limits.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > MAX_BUCKETS {
+            self.prune(&config, &mut buckets, now);
+        }
+
+        // Only take tokens when every bucket has one, so a denied request doesn't cost anything
+        let mut wait_time = Duration::ZERO;
+        for (key, limit) in &limits {
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| TokenBucket::new(limit));
+            bucket.refill(limit, now);
+            wait_time = wait_time.max(bucket.wait_time(limit));
+        }
+
+        if !wait_time.is_zero() {
+            self.logger.info(format!(
+                "Rate limited {} in {} on shard {} for {:.1}s",
+                user_id,
+                channel_id,
+                shard_id,
+                wait_time.as_secs_f64()
+            ));
+            return Err(wait_time);
+        }
+
+        for (key, _) in &limits {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the buckets that are full again, because they behave the same as new ones
+    fn prune(
+        &self,
+        config: &RateLimiterConfig,
+        buckets: &mut HashMap<String, TokenBucket>,
+        now: Instant,
+    ) {
+        buckets.retain(|key, bucket| {
+            let limit = if key.starts_with("user:") {
+                config.user_limit
+            } else if key.starts_with("channel:") {
+                config.channel_limit
+            } else {
+                config.global_limit
+            };
+
+            match limit {
+                Some(limit) => {
+                    bucket.refill(&limit, now);
+                    bucket.tokens < limit.capacity
+                }
+                None => false,
+            }
+        });
+    }
+}
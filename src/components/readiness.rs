@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// Keeps track of the Discord connection of every shard for the readiness check
+///
+/// # Fields
+///
+/// * `shards` - If the gateway of a shard is connected, per shard id
+#[derive(Default)]
+pub struct Readiness {
+    shards: RwLock<BTreeMap<u32, bool>>,
+}
+
+impl Readiness {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the Discord connection of a shard as up or down
+    ///
+    /// # Arguments
+    ///
+    /// * `shard_id` - The shard that connected or disconnected
+    /// * `ready` - If the gateway is connected
+    pub fn set_shard_ready(&self, shard_id: u32, ready: bool) {
+        self.shards.write().unwrap().insert(shard_id, ready);
+    }
+
+    /// If every shard of this process has received the `ready` event and is still connected
+    pub fn discord_ready(&self) -> bool {
+        let shards = self.shards.read().unwrap();
+        !shards.is_empty() && shards.values().all(|ready| *ready)
+    }
+
+    /// The shards that are not connected right now
+    pub fn disconnected_shards(&self) -> Vec<u32> {
+        self.shards
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, ready)| !**ready)
+            .map(|(shard_id, _)| *shard_id)
+            .collect()
+    }
+}
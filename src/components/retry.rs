@@ -0,0 +1,155 @@
+use super::{ConfigReader, Logger, Severity};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::fmt;
+use std::time::Duration;
+
+/// How often and how long to wait before a failed request is tried again
+///
+/// # Fields
+///
+/// * `max_retries` - How many times a request is tried again from `HTTP_MAX_RETRIES`
+/// * `base_delay` - The delay before the first retry from `HTTP_RETRY_BASE_DELAY_MS`. It
+///   doubles every retry
+/// * `max_delay` - The longest delay between retries from `HTTP_RETRY_MAX_DELAY_MS`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Reads the policy from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        Self {
+            max_retries: reader.number("HTTP_MAX_RETRIES", 2),
+            base_delay: Duration::from_millis(reader.number("HTTP_RETRY_BASE_DELAY_MS", 500)),
+            max_delay: Duration::from_millis(reader.number("HTTP_RETRY_MAX_DELAY_MS", 10_000)),
+        }
+    }
+
+    /// The delay before a retry with jitter, so multiple requests don't retry at the same time
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - The amount of retries that have already been done
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        delay.mul_f64(rand::random_range(0.5..=1.0))
+    }
+}
+
+/// Why a request failed
+///
+/// * `Http` - The request could not be sent or timed out
+/// * `Status` - The server responded with an error status and body
+#[derive(Debug)]
+pub enum RequestError {
+    Http(reqwest::Error),
+    Status(StatusCode, String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(why) => write!(f, "{}", why),
+            Self::Status(status, body) => write!(f, "{}: {}", status, body),
+        }
+    }
+}
+
+/// Reads `Retry-After` when it contains an amount of seconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Sends a request and tries it again with exponential backoff when it times out, can't
+/// connect or gets a 429 or 5xx status
+///
+/// # Arguments
+///
+/// * `policy` - How often and how long to wait before trying again
+/// * `timeout` - The timeout of every single attempt
+/// * `logger` - Used for logging the retries
+/// * `build` - Creates the request. It's called for every attempt
+///
+/// # Returns
+///
+/// The response with a successful status
+pub async fn send_with_retry<F>(
+    policy: &RetryPolicy,
+    timeout: Duration,
+    logger: &Logger,
+    build: F,
+) -> Result<Response, RequestError>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt = 0;
+
+    loop {
+        let (error, wait_time) = match build().timeout(timeout).send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let wait_time = retry_after(response.headers());
+                let body = response.text().await.unwrap_or_default();
+                let error = RequestError::Status(status, body);
+
+                if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+                    return Err(error);
+                }
+
+                (error, wait_time)
+            }
+            Err(why) => {
+                if !why.is_timeout() && !why.is_connect() {
+                    return Err(RequestError::Http(why));
+                }
+
+                (RequestError::Http(why), None)
+            }
+        };
+
+        if attempt >= policy.max_retries {
+            return Err(error);
+        }
+
+        // Waiting longer than the max delay would block the response for too long
+        let wait_time = match wait_time {
+            Some(wait_time) if wait_time > policy.max_delay => return Err(error),
+            Some(wait_time) => wait_time,
+            None => policy.backoff(attempt),
+        };
+
+        attempt += 1;
+        logger.warning(
+            format!(
+                "Request failed ({}). Retrying in {:.1}s ({}/{})",
+                error,
+                wait_time.as_secs_f64(),
+                attempt,
+                policy.max_retries
+            )
+            .as_str(),
+            Severity::Low,
+        );
+        tokio::time::sleep(wait_time).await;
+    }
+}
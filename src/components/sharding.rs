@@ -0,0 +1,111 @@
+use super::ConfigReader;
+use serenity::Client;
+use std::fmt;
+
+/// How the connection to Discord is split into shards
+///
+/// * `Single` - One shard, enough for bots in less than 2500 servers
+/// * `Auto` - The amount of shards Discord recommends, all in this process
+/// * `Range` - The shards `first` up to and including `last` of `total`. The other shards can
+///   run in other processes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sharding {
+    Single,
+    Auto,
+    Range { first: u32, last: u32, total: u32 },
+}
+
+impl Sharding {
+    /// Parses `auto`, a total like `4`, a range like `0-3/8` or a single shard like `2/8`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The shards as a string
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if value.eq_ignore_ascii_case("auto") {
+            return Some(Self::Auto);
+        }
+
+        let (shards, total) = match value.split_once('/') {
+            Some((shards, total)) => (shards.trim(), total.trim().parse::<u32>().ok()?),
+            None => {
+                let total = value.parse::<u32>().ok()?;
+                return (total > 0).then_some(Self::Range {
+                    first: 0,
+                    last: total - 1,
+                    total,
+                });
+            }
+        };
+
+        let (first, last) = match shards.split_once('-') {
+            Some((first, last)) => (
+                first.trim().parse::<u32>().ok()?,
+                last.trim().parse::<u32>().ok()?,
+            ),
+            None => {
+                let shard = shards.parse::<u32>().ok()?;
+                (shard, shard)
+            }
+        };
+
+        (first <= last && last < total).then_some(Self::Range { first, last, total })
+    }
+
+    /// Reads the shards from `SHARDS`. Uses a single shard when it has not been set
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        reader
+            .parse(
+                "SHARDS",
+                "auto, a total like 4, a range like 0-3/8 or a shard like 2/8",
+                Self::parse,
+            )
+            .unwrap_or(Self::Single)
+    }
+
+    /// The shards this process runs. Empty for `Auto`, because Discord decides the amount
+    pub fn shard_ids(&self) -> Vec<u32> {
+        match *self {
+            Self::Single => vec![0],
+            Self::Auto => Vec::new(),
+            Self::Range { first, last, .. } => (first..=last).collect(),
+        }
+    }
+
+    /// Connects the shards of this process to Discord. Returns when the client stops
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The Discord client
+    pub async fn start(&self, client: &mut Client) -> serenity::Result<()> {
+        match *self {
+            Self::Single => client.start().await,
+            Self::Auto => client.start_autosharded().await,
+            // Serenity treats the end of the range as the last shard to start
+            Self::Range { first, last, total } => {
+                client.start_shard_range(first..last, total).await
+            }
+        }
+    }
+}
+
+impl fmt::Display for Sharding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Single => write!(f, "a single shard"),
+            Self::Auto => write!(f, "the recommended amount of shards"),
+            Self::Range { first, last, total } if first == last => {
+                write!(f, "shard {} of {}", first, total)
+            }
+            Self::Range { first, last, total } => {
+                write!(f, "shards {} to {} of {}", first, last, total)
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use super::{HttpClient, Metrics, Readiness, SharedConfig, Shutdown};
+use crate::messages::AIDolly;
+use serenity::gateway::ShardManager;
+use serenity::prelude::TypeMapKey;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Makes the shard manager available through `Context::data`, so commands can read the
+/// gateway latency
+pub struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<ShardManager>;
+}
+
+/// State that is shared between the event handler and the slash commands
+///
+/// # Fields
+///
+/// * `config` - The settings of the bot, which can be reloaded while running
+/// * `ai_dolly` - The AI message handler, so commands can manage its conversation
+/// * `http` - The HTTP client that is shared by the AI providers and commands
+/// * `metrics` - The Prometheus metrics
+/// * `readiness` - If the bot is connected to Discord, used by `/readyz`
+/// * `shutdown` - The events that are being handled, so a shutdown can wait for them
+/// * `started_at` - When the bot started, used for the uptime
+pub struct SharedState {
+    pub config: Arc<SharedConfig>,
+    pub ai_dolly: Arc<AIDolly>,
+    pub http: Arc<HttpClient>,
+    pub metrics: Arc<Metrics>,
+    pub readiness: Arc<Readiness>,
+    pub shutdown: Arc<Shutdown>,
+    pub started_at: Instant,
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Keeps track of the events that are being handled, so a shutdown can wait for the replies
+/// and storage writes that are in progress
+///
+/// # Fields
+///
+/// * `shutting_down` - If new events are being ignored
+/// * `in_flight` - The amount of events that are being handled
+/// * `idle` - Wakes up the shutdown when the last event is done
+#[derive(Default)]
+pub struct Shutdown {
+    shutting_down: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// An event that is being handled. The shutdown waits until it's dropped
+pub struct InFlight {
+    shutdown: Arc<Shutdown>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.shutdown.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shutdown.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks an event as being handled until the returned value is dropped. Returns `None`
+    /// when the bot is shutting down, so the event should be ignored
+    pub fn start(self: &Arc<Self>) -> Option<InFlight> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        // Checked after counting it, so the shutdown can't miss an event that just started
+        let in_flight = InFlight {
+            shutdown: self.clone(),
+        };
+        if self.is_shutting_down() {
+            return None;
+        }
+
+        Some(in_flight)
+    }
+
+    /// If new events are being ignored
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// The amount of events that are being handled
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Stops accepting new events and waits until the ones in progress are done
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - How long to wait at most
+    ///
+    /// # Returns
+    ///
+    /// If every event finished before the deadline
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        self.shutting_down.store(true, Ordering::SeqCst);
+
+        let wait = async {
+            loop {
+                // Registered before checking, so a notification in between isn't lost
+                let idle = self.idle.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        };
+
+        tokio::time::timeout(deadline, wait).await.is_ok()
+    }
+}
@@ -1,25 +1,169 @@
 mod commands;
 mod components;
 mod messages;
-use crate::commands::{change_system_prompt, system_prompt};
-use commands::{clear_converstation, ping, quote, rage, version};
-use components::DotEnvReader;
+use commands::ask::Ask;
+use commands::change_system_prompt::ChangeSystemPrompt;
+use commands::clear_converstation::ClearConversation;
+use commands::ping::Ping as PingCommand;
+use commands::quote::Quote;
+use commands::rage::Rage;
+use commands::respond_mode::RespondModeCommand;
+use commands::status::Status;
+use commands::system_prompt::SystemPrompt;
+use commands::triggers::Triggers;
+use commands::usage::Usage;
+use commands::version::Version;
+use commands::{CommandContext, CommandRegistry, CommandResponse, CommandScope, CommandSync};
+use components::{
+    CommandPermissions, Config, ConfigReloader, DotEnvReader, HttpClient, HttpResponse, HttpServer,
+    Logger, Metrics, Readiness, Severity, ShardManagerContainer, SharedConfig, SharedState,
+    Shutdown,
+};
 use messages::{AIDolly, Insult, MessageHandler, Ping};
-use rust_logger::{Logger, Severity};
 use serenity::async_trait;
-use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage};
-use serenity::model::application::{Command, Interaction};
+use serenity::gateway::{ConnectionStage, ShardStageUpdateEvent};
+use serenity::http::Http;
+use serenity::model::application::Interaction;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
-use serenity::model::id::GuildId;
 use serenity::prelude::*;
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 use tokio::select;
 use tokio::signal;
 
 struct Handler {
     logger: Logger,
-    message_handlers: Vec<Box<dyn MessageHandler + Send + Sync>>,
+    message_handlers: Vec<Arc<dyn MessageHandler + Send + Sync>>,
+    commands: CommandRegistry,
+    command_sync: CommandSync,
+    commands_synced: AtomicBool,
+    command_permissions: Arc<CommandPermissions>,
+    state: SharedState,
+}
+
+impl Handler {
+    /// Sends the reply of a command back to Discord
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The context and interaction of the command
+    /// * `response` - The reply of the command
+    async fn send_response(&self, cmd: &CommandContext<'_>, response: CommandResponse) {
+        if let Err(why) = cmd.respond(response).await {
+            self.state
+                .metrics
+                .send_errors
+                .with_label_values(&["interaction"])
+                .inc();
+            self.logger.error(
+                format!("Cannot respond to slash command: {why}").as_str(),
+                Severity::High,
+            );
+        }
+    }
+
+    /// Brings the guild commands of `GUILD_IDS` and the global commands up to date. Commands
+    /// that are only enabled in some guilds are left out of the global commands and registered
+    /// in the guilds of their `<COMMAND>_ENABLED_GUILDS` instead
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - Used for talking to Discord
+    ///
+    /// # Returns
+    ///
+    /// If every scope could be synced
+    async fn sync_commands(&self, http: &Http) -> bool {
+        let guild_ids = self.state.config.get().guild_ids.clone();
+
+        for command in self.command_permissions.disabled_only() {
+            self.logger.warning(
+                format!(
+                    "'{}' is disabled in some servers, so it's only registered in GUILD_IDS",
+                    command
+                )
+                .as_str(),
+                Severity::Low,
+            );
+        }
+
+        // The guilds outside of GUILD_IDS only get the commands that are limited to them
+        let mut scopes: Vec<CommandScope> = guild_ids
+            .iter()
+            .chain(
+                self.command_permissions
+                    .enabled_guilds()
+                    .iter()
+                    .filter(|guild_id| !guild_ids.contains(guild_id)),
+            )
+            .map(|guild_id| CommandScope::Guild(*guild_id))
+            .collect();
+        scopes.push(CommandScope::Global);
+
+        let mut synced = true;
+        for scope in scopes {
+            let commands = self
+                .commands
+                .create_commands(&self.command_permissions, |name| match scope {
+                    CommandScope::Global => !self.command_permissions.is_guild_limited(name),
+                    CommandScope::Guild(guild_id) => {
+                        self.command_permissions.is_enabled(name, Some(guild_id))
+                            && (guild_ids.contains(&guild_id)
+                                || self.command_permissions.is_guild_limited(name))
+                    }
+                });
+
+            match self.command_sync.sync(http, scope, commands).await {
+                Ok(summary) => self
+                    .logger
+                    .info(format!("Synced the {} commands: {}", scope, summary).as_str()),
+                Err(why) => {
+                    synced = false;
+                    self.logger.error(
+                        format!("Could not sync the {} commands: {}", scope, why).as_str(),
+                        Severity::High,
+                    );
+                }
+            }
+        }
+
+        synced
+    }
+}
+
+/// Deletes every global command and the guild commands of `GUILD_IDS` and every
+/// `<COMMAND>_ENABLED_GUILDS` without connecting to the gateway. Used by `--purge-commands`
+///
+/// # Arguments
+///
+/// * `config` - The loaded config
+/// * `logger` - Used for logging information and errors
+async fn purge_commands(config: &Config, logger: &Logger) -> serenity::Result<()> {
+    let http = Http::new(&config.discord_token);
+    let application = http.get_current_application_info().await?;
+    http.set_application_id(application.id);
+
+    let mut guild_ids = config.guild_ids.clone();
+    for rule in config.command_rules.values() {
+        guild_ids.extend(&rule.enabled_guilds);
+    }
+    guild_ids.sort();
+    guild_ids.dedup();
+
+    let command_sync = CommandSync::new();
+    for guild_id in guild_ids {
+        command_sync
+            .purge(&http, CommandScope::Guild(guild_id))
+            .await?;
+    }
+    command_sync.purge(&http, CommandScope::Global).await?;
+
+    logger.info("Purged every command. They are registered again on the next start");
+    Ok(())
 }
 
 #[async_trait]
@@ -30,10 +174,25 @@ impl EventHandler for Handler {
             return;
         }
 
+        // New messages are ignored while shutting down
+        let Some(_in_flight) = self.state.shutdown.start() else {
+            return;
+        };
+
         self.logger.info(format!("Received: {}", msg.content));
+        self.state
+            .metrics
+            .messages_received
+            .with_label_values(&[&ctx.shard_id.to_string()])
+            .inc();
 
         for handler in self.message_handlers.iter() {
             if handler.respond(&ctx, &msg).await {
+                self.state
+                    .metrics
+                    .handler_matches
+                    .with_label_values(&[handler.name()])
+                    .inc();
                 return;
             }
         }
@@ -41,156 +200,289 @@ impl EventHandler for Handler {
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         if let Interaction::Command(command) = interaction {
-            self.logger
-                .debug(format!("Received command interaction: {command:#?}").as_str());
-
-            let content = match command.data.name.as_str() {
-                "ping" => Some(commands::ping::run(&command.data.options())),
-                "rage" => Some(commands::rage::run(&command.data.options())),
-                "quote" => {
-                    Some(commands::quote::run(Logger::new("Quote"), &command.data.options()).await)
-                }
-                "clearconversation" => Some(commands::clear_converstation::run(
-                    &command.data.options(),
-                    self.message_handlers.last().unwrap().as_ref(),
-                )),
-                "version" => Some(commands::version::run(&command.data.options())),
-                "system_prompt" => Some(commands::system_prompt::run(&command.data.options())),
-                "change_system_prompt" => {
-                    Some(commands::change_system_prompt::run(&command.data.options()))
+            let Some(_in_flight) = self.state.shutdown.start() else {
+                return;
+            };
+
+            // The interaction token can be used to reply, so it stays out of the logs
+            self.logger.debug(
+                format!("Received command interaction: {command:#?}")
+                    .replace(&command.token, "[redacted]")
+                    .as_str(),
+            );
+
+            let cmd = CommandContext::new(&ctx, &command, &self.state);
+
+            if let Err(reason) = self.command_permissions.check(&command) {
+                self.send_response(&cmd, CommandResponse::text(reason).ephemeral())
+                    .await;
+                return;
+            }
+
+            let response = match self.commands.get(command.data.name.as_str()) {
+                Some(slash_command) => {
+                    self.state
+                        .metrics
+                        .slash_commands
+                        .with_label_values(&[slash_command.name()])
+                        .inc();
+                    slash_command.run(&cmd).await
                 }
-                _ => {
+                None => {
                     self.logger.warning(
                         format!("Invalid command: {}", command.data.name.as_str()).as_str(),
                         Severity::Medium,
                     );
-                    Some("not implemented :(".to_string())
+                    CommandResponse::text("not implemented :(")
                 }
             };
 
-            if let Some(content) = content {
-                let data = CreateInteractionResponseMessage::new().content(content);
-                let builder = CreateInteractionResponse::Message(data);
-
-                if let Err(why) = command.create_response(&ctx.http, builder).await {
-                    self.logger.error(
-                        format!("Cannot respond to slash command: {why}").as_str(),
-                        Severity::High,
-                    );
-                }
-            }
+            self.send_response(&cmd, response).await;
         }
     }
 
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        self.logger
-            .info(format!("{} is connected!", ready.user.name).as_str());
-
-        let guild_id = match env::var("GUILD_ID") {
-            Ok(value) => {
-                match value.parse::<u64>() {
-                    Ok(parsed) => GuildId::new(parsed),
-                    Err(_) => {
-                        self.logger.warning(
-                            "GUILD_ID is not a valid number. Defaulting to '0'",
-                            Severity::Medium,
-                        );
-                        GuildId::new(0) // Default to 0 if parsing fails
-                    }
-                }
-            }
-            Err(_) => {
-                self.logger
-                    .debug("GUILD_ID has not been set in the environment. Defaulting to '0'");
-                GuildId::new(0) // Default to 0 if the environment variable is not set
-            }
-        };
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        self.logger.debug(
+            format!(
+                "Shard {} went from {:?} to {:?}",
+                event.shard_id, event.old, event.new
+            )
+            .as_str(),
+        );
+        let connected = event.new == ConnectionStage::Connected;
+        self.state
+            .readiness
+            .set_shard_ready(event.shard_id.0, connected);
+        self.state
+            .metrics
+            .shard_connected
+            .with_label_values(&[&event.shard_id.to_string()])
+            .set(connected as i64);
+    }
 
-        // Only if guild_id is not 0 then create the guild commands
-        if guild_id != 0 {
-            let commands = guild_id
-                .set_commands(
-                    &ctx.http,
-                    [
-                        ping::register(),
-                        rage::register(),
-                        quote::register(),
-                        version::register(),
-                        clear_converstation::register(),
-                        system_prompt::register(),
-                        change_system_prompt::register(),
-                    ]
-                    .to_vec(),
-                )
-                .await;
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        self.logger.info(
+            format!(
+                "{} is connected on shard {}!",
+                ready.user.name, ctx.shard_id
+            )
+            .as_str(),
+        );
+        self.state.readiness.set_shard_ready(ctx.shard_id.0, true);
+        self.state
+            .metrics
+            .shard_connected
+            .with_label_values(&[&ctx.shard_id.to_string()])
+            .set(1);
 
-            self.logger.debug(
-                format!("I now have the following guild slash commands: {commands:#?}").as_str(),
-            );
+        // Every shard and every reconnect sends `ready`, but the commands only change on a
+        // restart. A failed sync is tried again on the next `ready`
+        if self.commands_synced.swap(true, Ordering::SeqCst) {
+            return;
         }
 
-        // Global commands
-        let global_commands = [
-            Command::create_global_command(&ctx.http, ping::register()).await,
-            Command::create_global_command(&ctx.http, rage::register()).await,
-            Command::create_global_command(&ctx.http, quote::register()).await,
-            Command::create_global_command(&ctx.http, version::register()).await,
-            Command::create_global_command(&ctx.http, system_prompt::register()).await,
-            Command::create_global_command(&ctx.http, change_system_prompt::register()).await,
-            Command::create_global_command(&ctx.http, clear_converstation::register()).await,
-        ];
-
-        for global_command in global_commands {
-            self.logger.debug(
-                format!("I created the following global slash command: {global_command:#?}")
-                    .as_str(),
-            );
+        if !self.sync_commands(&ctx.http).await {
+            self.commands_synced.store(false, Ordering::SeqCst);
         }
     }
 }
 
 #[tokio::main]
 async fn main() {
-    let dotenv = DotEnvReader::new(".env");
+    // A reload has to tell the real environment apart from what came from .env
+    let environment: HashMap<String, String> = env::vars().collect();
+
+    let dotenv = DotEnvReader::new(".env", &environment);
     dotenv.parse_and_set_env();
 
     let logger = Logger::new("Main");
     logger.info("Starting up");
 
-    // Handle .env
-    let token = env::var("DISCORD_TOKEN").unwrap_or_else(|_| {
-        logger.error("DISCORD_TOKEN not found in environment", Severity::Critical);
+    let mut commands = CommandRegistry::new();
+    commands.add(PingCommand);
+    commands.add(Rage);
+    commands.add(Quote::new());
+    commands.add(Version);
+    commands.add(ClearConversation);
+    commands.add(SystemPrompt);
+    commands.add(ChangeSystemPrompt);
+    commands.add(Ask::new());
+    commands.add(Triggers);
+    commands.add(RespondModeCommand);
+    commands.add(Usage);
+    commands.add(Status);
+
+    // The permission settings are read per command name
+    let command_names: Vec<&'static str> = commands.iter().map(|command| command.name()).collect();
+
+    // Handle dolly.toml, .env and the environment
+    let config = Config::load(&environment, &command_names).unwrap_or_else(|errors| {
+        for error in &errors {
+            logger.error(error, Severity::Critical);
+        }
+        logger.error(
+            format!("Found {} invalid settings, shutting down", errors.len()).as_str(),
+            Severity::Critical,
+        );
         std::process::exit(1);
     });
+    let shared_config = Arc::new(SharedConfig::new(config));
+    let config = shared_config.get();
 
-    let intents = GatewayIntents::GUILD_MESSAGES
+    if env::args().any(|arg| arg == "--purge-commands") {
+        if let Err(why) = purge_commands(&config, &logger).await {
+            logger.error(
+                format!("Could not purge the commands: {}", why).as_str(),
+                Severity::Critical,
+            );
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
 
-    let mut handlers: Vec<Box<dyn MessageHandler + Send + Sync>> = Vec::new();
+    let mut handlers: Vec<Arc<dyn MessageHandler + Send + Sync>> = Vec::new();
+
+    let http = Arc::new(HttpClient::new(&config.http));
+    let metrics = Arc::new(Metrics::new());
+    let readiness = Arc::new(Readiness::new());
+    // Known up front, so one connected shard doesn't make the whole process ready
+    for shard_id in config.sharding.shard_ids() {
+        readiness.set_shard_ready(shard_id, false);
+    }
+    let shutdown = Arc::new(Shutdown::new());
 
     let insult_handler = Insult::new();
-    let ai_dolly_handler = AIDolly::new();
+    let ai_dolly_handler = Arc::new(AIDolly::new(&config, http.clone(), metrics.clone()));
     let ping_handler = Ping::new();
 
-    handlers.push(Box::new(insult_handler));
-    handlers.push(Box::new(ping_handler));
+    handlers.push(Arc::new(insult_handler));
+    handlers.push(Arc::new(ping_handler));
 
     // AI dolly should always be last
-    handlers.push(Box::new(ai_dolly_handler));
+    handlers.push(ai_dolly_handler.clone());
+
+    ai_dolly_handler.ollama_health().clone().spawn();
 
-    let mut client = Client::builder(&token, intents)
+    // Metrics and health checks
+    match config.listen_addr {
+        Some(address) => {
+            let metrics = metrics.clone();
+            let readiness = readiness.clone();
+            let shutdown = shutdown.clone();
+            let ai_dolly = ai_dolly_handler.clone();
+
+            HttpServer::new()
+                .route("/metrics", move || HttpResponse {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4; charset=utf-8",
+                    body: metrics.render(),
+                })
+                .route("/healthz", || HttpResponse::text(200, "ok\n"))
+                .route("/readyz", move || {
+                    let mut problems = Vec::new();
+
+                    if shutdown.is_shutting_down() {
+                        problems.push("Shutting down".to_string());
+                    }
+
+                    if !readiness.discord_ready() {
+                        let disconnected = readiness.disconnected_shards();
+                        if disconnected.is_empty() {
+                            problems.push("Not connected to Discord".to_string());
+                        } else {
+                            let shards: Vec<String> =
+                                disconnected.iter().map(|id| id.to_string()).collect();
+                            problems.push(format!(
+                                "Not connected to Discord on shards {}",
+                                shards.join(", ")
+                            ));
+                        }
+                    }
+
+                    if ai_dolly.healthy_providers().is_empty() {
+                        let configured: Vec<&str> = ai_dolly
+                            .configured_providers()
+                            .iter()
+                            .map(|provider| provider.as_str())
+                            .collect();
+                        problems.push(format!(
+                            "No healthy AI provider (configured: {})",
+                            configured.join(", ")
+                        ));
+                    }
+
+                    if problems.is_empty() {
+                        HttpResponse::text(200, "ready\n")
+                    } else {
+                        HttpResponse::text(503, problems.join("\n") + "\n")
+                    }
+                })
+                .spawn(address.to_string());
+        }
+        None => {
+            logger.debug("LISTEN_ADDR has not been set. Not exposing metrics and health checks")
+        }
+    }
+
+    let mut command_permissions = CommandPermissions::new(config.command_rules.clone());
+    for command in commands.iter() {
+        command_permissions.require(command.name(), command.permissions());
+    }
+
+    let command_permissions = Arc::new(command_permissions);
+    // Reloaded on SIGHUP
+    #[cfg(unix)]
+    let config_reloader = ConfigReloader::new(
+        environment,
+        command_names,
+        shared_config.clone(),
+        ai_dolly_handler.clone(),
+        command_permissions.clone(),
+    );
+
+    let state = SharedState {
+        config: shared_config.clone(),
+        ai_dolly: ai_dolly_handler,
+        http,
+        metrics,
+        readiness,
+        shutdown: shutdown.clone(),
+        started_at: Instant::now(),
+    };
+
+    let mut client = Client::builder(&config.discord_token, intents)
         .event_handler(Handler {
             logger: Logger::new("Handler"),
             message_handlers: handlers,
+            commands,
+            command_sync: CommandSync::new(),
+            commands_synced: AtomicBool::new(false),
+            command_permissions,
+            state,
         })
         .await
         .expect("Err creating client");
 
-    let client_start_future = client.start();
+    client
+        .data
+        .write()
+        .await
+        .insert::<ShardManagerContainer>(client.shard_manager.clone());
+
+    let shard_manager = client.shard_manager.clone();
+    logger.info(format!("Starting {}", config.sharding).as_str());
+    let sharding = config.sharding;
+    let client_start_future = sharding.start(&mut client);
+    tokio::pin!(client_start_future);
 
     // Handle signals
     let ctrl_c_future = signal::ctrl_c();
+    tokio::pin!(ctrl_c_future);
 
     #[cfg(unix)]
     let mut sigterm_signal =
@@ -198,20 +490,30 @@ async fn main() {
             .expect("Unable to create SIGTERM listener");
 
     #[cfg(unix)]
-    let sigterm_future = sigterm_signal.recv();
+    let mut sighup_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Unable to create SIGHUP listener");
 
     // Use a conditional compilation to make it cross-platform
     #[cfg(unix)]
-    select! {
-        _ = client_start_future => {
-            logger.info("Client has shut down.");
-        },
-        _ = ctrl_c_future => {
-            logger.info("Ctrl-C has been pressed, shutting down...");
-        },
-        _ = sigterm_future => {
-            logger.info("SIGTERM received, shutting down...");
-        },
+    loop {
+        select! {
+            _ = &mut client_start_future => {
+                logger.info("Client has shut down.");
+                break;
+            },
+            _ = &mut ctrl_c_future => {
+                logger.info("Ctrl-C has been pressed, shutting down...");
+                break;
+            },
+            _ = sigterm_signal.recv() => {
+                logger.info("SIGTERM received, shutting down...");
+                break;
+            },
+            _ = sighup_signal.recv() => {
+                logger.info("SIGHUP received, reloading the config...");
+                config_reloader.reload();
+            },
+        }
     }
 
     #[cfg(not(unix))]
@@ -224,5 +526,28 @@ async fn main() {
         },
     }
 
+    // Stop taking new messages and give the replies in progress time to finish
+    let timeout = shared_config.get().shutdown_timeout;
+    if shutdown.in_flight() > 0 {
+        logger.info(format!(
+            "Waiting up to {} seconds for {} replies in progress",
+            timeout.as_secs(),
+            shutdown.in_flight()
+        ));
+    }
+
+    if !shutdown.drain(timeout).await {
+        logger.warning(
+            format!(
+                "Stopped waiting with {} replies still in progress",
+                shutdown.in_flight()
+            )
+            .as_str(),
+            Severity::Medium,
+        );
+    }
+
+    shard_manager.shutdown_all().await;
+
     logger.info("Application shutting down gracefully.");
 }
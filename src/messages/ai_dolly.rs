@@ -1,14 +1,24 @@
 use super::message_handler::MessageHandler;
-use super::{Cohere, LlmProvider, MessageRequest, Ollama, OpenAI};
-use rust_logger::{Logger, Severity};
+use super::{
+    BudgetAction, Cohere, LlmProvider, MessageRequest, Ollama, OllamaHealth, OpenAI, Provider,
+    ProviderStats, ReplyPolicy, RequestOptions, RespondMode, ResponseCache, ResponseSettings,
+    TriggerSet, UsageTracker,
+};
+use crate::components::{
+    CircuitBreaker, Config, HttpClient, Logger, Metrics, RateLimiter, Severity, write_atomic,
+};
 use serde::{Deserialize, Serialize};
 use serenity::async_trait;
 use serenity::client::Context;
 use serenity::model::channel::Message;
-use std::fs::{OpenOptions, read_to_string};
-use std::io::Write;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::user::User;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::read_to_string;
 use std::path::Path;
-use std::{env, fs};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 /// This type contains some settings for Ollama
 ///
@@ -25,9 +35,72 @@ pub struct LlmBody {
 }
 
 /// Ollama response as a string
+///
+/// # Fields
+///
+/// * `message` - The message of the AI
+/// * `usage` - The amount of tokens that were used, if the provider reported it
+/// * `error` - Why the provider could not respond
 #[derive(Debug, Clone, Deserialize)]
 pub struct LlmResponse {
     pub message: LlmMessage,
+    #[serde(skip)]
+    pub usage: Option<LlmUsage>,
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl LlmResponse {
+    /// A response without a role or content, used when a provider could not respond
+    pub fn empty() -> Self {
+        Self {
+            message: LlmMessage {
+                content: String::new(),
+                role: String::new(),
+            },
+            usage: None,
+            error: None,
+        }
+    }
+
+    /// An empty response with the reason why the provider could not respond
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - Why the provider could not respond
+    pub fn failed(error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::empty()
+        }
+    }
+}
+
+/// The amount of tokens a request used
+///
+/// # Fields
+///
+/// * `input_tokens` - The tokens of the prompt
+/// * `output_tokens` - The tokens of the response
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct LlmUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// A response together with the provider that generated it
+///
+/// # Fields
+///
+/// * `provider` - The provider that responded
+/// * `model` - The model that responded
+/// * `response` - The response itself
+/// * `cached` - If the response came from the cache instead of the provider
+struct ProviderResponse {
+    provider: Provider,
+    model: String,
+    response: LlmResponse,
+    cached: bool,
 }
 
 /// Ollama message stored in the json file
@@ -42,14 +115,79 @@ pub struct LlmMessage {
     pub role: String,
 }
 
+/// Someone that has talked in the conversation
+///
+/// # fields
+///
+/// * `id` - The Discord user id
+/// * `name` - The display name of the user
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Participant {
+    pub id: String,
+    pub name: String,
+}
+
 /// The whole conversation that gets stored
 ///
 /// # fields
 ///
 /// * `messages` - A vector of OllamaMessages
+/// * `participants` - Everyone that has talked in the conversation
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Conversation {
     pub messages: Vec<LlmMessage>,
+    #[serde(default)]
+    pub participants: Vec<Participant>,
+}
+
+/// Where a prompt for the AI comes from. Either a chat message or a slash command
+///
+/// # fields
+///
+/// * `author` - The user that wrote the prompt
+/// * `content` - The prompt itself
+/// * `channel_id` - The channel the prompt was written in
+/// * `guild_id` - The server the prompt was written in. `None` in direct messages
+/// * `mentions` - The users that got pinged inside of the prompt
+pub struct PromptSource<'a> {
+    pub author: &'a User,
+    pub content: &'a str,
+    pub channel_id: ChannelId,
+    pub guild_id: Option<GuildId>,
+    pub mentions: &'a [User],
+}
+
+impl<'a> PromptSource<'a> {
+    /// Creates a prompt source from a chat message
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The received Discord message
+    pub fn from_message(msg: &'a Message) -> Self {
+        Self {
+            author: &msg.author,
+            content: &msg.content,
+            channel_id: msg.channel_id,
+            guild_id: msg.guild_id,
+            mentions: &msg.mentions,
+        }
+    }
+}
+
+/// The values that replace the placeholders inside of the system prompt
+///
+/// # fields
+///
+/// * `bot_name` - Replaces `{{bot_name}}`
+/// * `guild_name` - Replaces `{{guild_name}}`
+/// * `channel_name` - Replaces `{{channel_name}}`
+/// * `participants` - Replaces `{{participants}}`
+#[derive(Debug, Clone)]
+pub struct PromptVariables {
+    pub bot_name: String,
+    pub guild_name: String,
+    pub channel_name: String,
+    pub participants: String,
 }
 
 impl Conversation {
@@ -69,6 +207,21 @@ impl Conversation {
         self.messages.push(ollama_message);
     }
 
+    /// Adds the user to the participants or updates their name if they are already in there
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The Discord user that sent a message
+    fn add_participant(&mut self, user: &User) {
+        let id = user.id.to_string();
+        let name = user.display_name().to_string();
+
+        match self.participants.iter_mut().find(|p| p.id == id) {
+            Some(participant) => participant.name = name,
+            None => self.participants.push(Participant { id, name }),
+        }
+    }
+
     fn trim_messages(&mut self, max_messages: i32) {
         if max_messages == 0 {
             return;
@@ -91,25 +244,51 @@ impl Conversation {
     }
 }
 
-/// This type will communicate with the Ollama api
+/// How the bot lets someone know they are sending too many messages
+///
+/// * `Reaction` - Reacts with ⏳ on the message
+/// * `Message` - Replies with how long they have to wait
+/// * `Silent` - Ignores the message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CooldownReply {
+    Reaction,
+    Message,
+    Silent,
+}
+
+/// Creates the friendly message for someone that is being rate limited
+///
+/// # Arguments
+///
+/// * `wait_time` - How long they have to wait
+pub fn cooldown_message(wait_time: Duration) -> String {
+    format!(
+        "Slow down! You can talk to me again in {} seconds ⏳",
+        wait_time.as_secs_f64().ceil() as u64
+    )
+}
+
+/// The provider settings of `AIDolly` that are replaced together on a reload
 ///
 /// # fields
 ///
-/// `logger` - Used for logging information and errors
 /// `ollama_base_url` - The url to the ollama server
-/// `respond_to_all_messages` - A boolean to send messages to all messages it receives
-/// `responds_to_vec` - This is the type of messages it will always respond to
 /// `ollama_model` - The model that's gonna be used. Like `llama3.1`
-/// `out_dir` - The output directory of the json file
-/// `conversation_file` - The json file where it contains the whole conversation
-pub struct AIDolly {
-    logger: Logger,
+/// `ollama_num_ctx` - The context window of Ollama
+/// `cooldown_reply` - How the bot responds when someone is rate limited
+/// `request_options` - The timeout and retries per provider
+/// `max_stored_messages` - The max messages of the conversation
+/// `openai_model` - The model of OpenAI
+/// `openai_token` - The token of OpenAI. Empty when it's not used
+/// `cohere_model` - The model of Cohere
+/// `cohere_token` - The token of Cohere. Empty when it's not used
+/// `priortize_ollama` - If Ollama is tried before the paid providers
+struct ProviderSettings {
     ollama_base_url: String,
-    respond_to_all_messages: bool,
-    responds_to_vec: Vec<String>,
     ollama_model: String,
-    out_dir: String,
-    conversation_file: String,
+    ollama_num_ctx: u32,
+    cooldown_reply: CooldownReply,
+    request_options: HashMap<Provider, RequestOptions>,
     max_stored_messages: i32,
     openai_model: String,
     openai_token: String,
@@ -118,144 +297,159 @@ pub struct AIDolly {
     priortize_ollama: bool,
 }
 
-impl AIDolly {
-    /// Constructor
-    pub fn new() -> Self {
-        let out_dir = "out_data".to_string();
-        let conversation_file = "conversation.json".to_string();
-        let logger = Logger::new("AIdolly");
-
-        // Ollama URL
-        let ollama_base_url = env::var("OLLAMA_URL").unwrap_or_else(|_| {
-            logger.error(
-                "OLLAMA_URL has not been set in the environment",
-                Severity::High,
-            );
-            "".to_string()
-        });
-
-        // Validate URL
-        let ollama_base_url = if ollama_base_url.starts_with("http") {
-            ollama_base_url
-        } else {
-            logger.error(
-                "OLLAMA_URL does not contain 'http' or 'https' scheme",
-                Severity::High,
-            );
-            "".to_string()
-        };
-
-        // Ollama model
-        let ollama_model = env::var("OLLAMA_MODEL").unwrap_or_else(|_| {
-            let default_model = "llama3.1".to_string();
-            logger.error(
-                format!(
-                    "OLLAMA_MODEL has not been set in the environment. Defaulting to {}",
-                    default_model
-                )
-                .as_str(),
-                Severity::Medium,
+impl ProviderSettings {
+    fn new(config: &Config) -> Self {
+        let mut request_options = HashMap::new();
+        for (provider, timeout) in [
+            (Provider::Ollama, config.ollama.timeout),
+            (Provider::OpenAI, config.openai.timeout),
+            (Provider::Cohere, config.cohere.timeout),
+        ] {
+            request_options.insert(
+                provider,
+                RequestOptions {
+                    timeout,
+                    retry: config.retry,
+                },
             );
-            default_model
-        });
-
-        // Max stored messages
-        let max_stored_messages: i32 = env::var("MAX_STORED_MESSAGES")
-            .unwrap_or_else(|_| {
-                let default_amount = 6;
-                logger.error(
-                    format!(
-                        "MAX_STORED_MESSAGES has not been set in the environment. Defaulting to {}",
-                        default_amount
-                    )
-                    .as_str(),
-                    Severity::Medium,
-                );
-                default_amount.to_string() // Convert to String for parse.
-            })
-            .parse()
-            .unwrap_or_else(|_| {
-                let fallback_amount = 6;
-                logger.error(
-                    format!(
-                        "MAX_STORED_MESSAGES is an invalid number. Defaulting to {}",
-                        fallback_amount
-                    )
-                    .as_str(),
-                    Severity::Medium,
-                );
-                fallback_amount
-            });
-
-        // Responds to
-        let responds_to = env::var("RESPONDS_TO")
-            .unwrap_or_else(|_| {
-                let default_responds_to = "dolly".to_string();
-                logger.warning(
-                    format!(
-                        "RESPONDS_TO has not been set in the environment. Defaulting to '{}'",
-                        default_responds_to
-                    )
-                    .as_str(),
-                    Severity::Low,
-                );
-                default_responds_to
-            })
-            .to_lowercase();
-
-        let mut responds_to_vec: Vec<String> = Vec::new();
-        for respond in responds_to.split(",") {
-            responds_to_vec.push(respond.to_lowercase().to_string());
         }
 
-        // Respond to all messages
-        let respond_to_all_messages = env::var("RESPOND_TO_ALL_MESSAGES")
-            .unwrap_or_else(|_| {
-                logger.warning(
-                    "RESPOND_TO_ALL_MESSAGES has not been set in the environment. Defaulting to 'false'",
+        Self {
+            ollama_base_url: config.ollama.url.clone(),
+            ollama_model: config.ollama.model.clone(),
+            ollama_num_ctx: config.ollama.num_ctx,
+            cooldown_reply: config.cooldown_reply,
+            request_options,
+            max_stored_messages: config.max_stored_messages,
+            openai_token: config.openai.token.clone(),
+            openai_model: config.openai.model.clone(),
+            cohere_token: config.cohere.token.clone(),
+            cohere_model: config.cohere.model.clone(),
+            priortize_ollama: config.prioritize_ollama,
+        }
+    }
+}
 
-                    Severity::Low,
-                );
-                "false".to_string()
-            })
-            .to_lowercase() == "true";
+/// The respond mode that applies when a guild or channel hasn't changed it
+///
+/// # Arguments
+///
+/// * `config` - The settings of the bot
+fn default_mode(config: &Config) -> RespondMode {
+    if config.respond_to_all_messages {
+        RespondMode::All
+    } else {
+        RespondMode::Triggers
+    }
+}
 
-        // OpenAI Token
-        let openai_token = env::var("OPENAI_TOKEN").unwrap_or_else(|_| "".to_string());
+/// This type will communicate with the Ollama api
+///
+/// # fields
+///
+/// `logger` - Used for logging information and errors
+/// `settings` - The urls, tokens, models and request options of the providers
+/// `response_settings` - The trigger words and respond mode per guild and channel
+/// `reply_policy` - The guilds, channels and direct messages the AI is allowed to reply in
+/// `rate_limiter` - Limits how often the AI can be used
+/// `http` - The shared HTTP client used for the providers
+/// `metrics` - The Prometheus metrics
+/// `ollama_health` - Checks if the Ollama server is reachable and has the model
+/// `circuit_breakers` - Skips a provider for a while after it failed too many times in a row
+/// `provider_stats` - The latency, successes and errors per provider
+/// `response_cache` - Stores the responses of identical prompts for a while
+/// `usage_tracker` - Keeps track of the tokens and cost per provider, guild and user
+/// `out_dir` - The output directory of the json file
+/// `conversation_file` - The json file where it contains the whole conversation
+pub struct AIDolly {
+    logger: Logger,
+    settings: RwLock<Arc<ProviderSettings>>,
+    response_settings: ResponseSettings,
+    reply_policy: ReplyPolicy,
+    rate_limiter: RateLimiter,
+    http: Arc<HttpClient>,
+    metrics: Arc<Metrics>,
+    ollama_health: Arc<OllamaHealth>,
+    circuit_breakers: HashMap<Provider, CircuitBreaker>,
+    provider_stats: ProviderStats,
+    response_cache: ResponseCache,
+    usage_tracker: UsageTracker,
+    out_dir: String,
+    conversation_file: String,
+}
 
-        // OpenAI Model
-        let openai_model = env::var("OPENAI_MODEL").unwrap_or_else(|_| "gpt-4o".to_string());
+impl AIDolly {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the bot
+    /// * `http` - The shared HTTP client used for the providers
+    /// * `metrics` - The Prometheus metrics
+    pub fn new(config: &Config, http: Arc<HttpClient>, metrics: Arc<Metrics>) -> Self {
+        let out_dir = "out_data".to_string();
+        let conversation_file = "conversation.json".to_string();
 
-        // Cohere Token
-        let cohere_token = env::var("COHERE_TOKEN").unwrap_or_else(|_| "".to_string());
+        let response_settings =
+            ResponseSettings::new(&out_dir, config.responds_to.clone(), default_mode(config));
 
-        // Cohere Model
-        let cohere_model =
-            env::var("COHERE_MODEL").unwrap_or_else(|_| "command-r-plus-08-2024".to_string());
+        let circuit_breakers = [Provider::Ollama, Provider::OpenAI, Provider::Cohere]
+            .into_iter()
+            .map(|provider| {
+                (
+                    provider,
+                    CircuitBreaker::new(&format!("{:?}", provider), config.circuit_breaker),
+                )
+            })
+            .collect();
 
-        // Prioritize Ollama
-        let priortize_ollama = env::var("PRIORTIZE_OLLAMA")
-            .unwrap_or_else(|_| "true".to_string())
-            .to_lowercase()
-            == "true";
+        let ollama_health = Arc::new(OllamaHealth::new(http.clone(), &config.ollama));
 
         Self {
-            logger,
-            ollama_base_url,
-            ollama_model,
-            responds_to_vec,
-            respond_to_all_messages,
+            logger: Logger::new("AIdolly"),
+            settings: RwLock::new(Arc::new(ProviderSettings::new(config))),
+            response_settings,
+            reply_policy: ReplyPolicy::new(&config.reply_policy),
+            rate_limiter: RateLimiter::new(&config.rate_limits),
+            http,
+            metrics,
+            ollama_health,
+            circuit_breakers,
+            provider_stats: ProviderStats::new(),
+            response_cache: ResponseCache::new(&config.response_cache),
+            usage_tracker: UsageTracker::new(&out_dir, &config.usage),
             conversation_file,
             out_dir,
-            max_stored_messages,
-            openai_token,
-            openai_model,
-            cohere_token,
-            cohere_model,
-            priortize_ollama,
         }
     }
 
+    /// Applies a new config to the providers, triggers, reply policy, rate limits, cache,
+    /// budgets and circuit breakers. Conversations, usage and stored overrides are kept
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new settings of the bot
+    pub fn reload(&self, config: &Config) {
+        *self.settings.write().unwrap() = Arc::new(ProviderSettings::new(config));
+        self.response_settings
+            .set_defaults(config.responds_to.clone(), default_mode(config));
+        self.reply_policy.reload(&config.reply_policy);
+        self.rate_limiter.reload(&config.rate_limits);
+        self.response_cache.reload(&config.response_cache);
+        self.usage_tracker.reload(&config.usage);
+        self.ollama_health.reload(&config.ollama);
+
+        for circuit_breaker in self.circuit_breakers.values() {
+            circuit_breaker.reload(config.circuit_breaker);
+        }
+    }
+
+    /// The provider settings of right now. A reload doesn't change a request that already
+    /// started
+    fn settings(&self) -> Arc<ProviderSettings> {
+        self.settings.read().unwrap().clone()
+    }
+
     /// This function will save the conversation to a json file
     ///
     /// # Arguments
@@ -273,22 +467,11 @@ impl AIDolly {
             return;
         }
 
-        // Overwrite file
-        let file = OpenOptions::new()
-            .write(true)
-            .append(false)
-            .create(true)
-            .truncate(true)
-            .open(dir_path.join(self.conversation_file.clone()));
-
         let json_string = serde_json::to_string_pretty(&conversation).unwrap();
 
-        match file {
-            Ok(mut f) => {
-                let _ = f.write_all(json_string.as_bytes());
-                self.logger.info("Saved conversation");
-                drop(f)
-            }
+        // Overwrite file
+        match write_atomic(&dir_path.join(self.conversation_file.clone()), &json_string) {
+            Ok(()) => self.logger.info("Saved conversation"),
             Err(e) => self.logger.error(
                 format!("Could not save conversation: '{}'", e).as_str(),
                 Severity::High,
@@ -325,6 +508,7 @@ impl AIDolly {
 
         Conversation {
             messages: Vec::new(),
+            participants: Vec::new(),
         }
     }
 
@@ -358,84 +542,312 @@ impl AIDolly {
         }
     }
 
-    async fn get_llm_message_based_on_settings(&self, llm_body: LlmBody) -> LlmResponse {
-        if self.priortize_ollama {
-            self.logger.info("Using Ollama to respond");
-            let ollama_response = Ollama::get_message(
-                MessageRequest::WithUrl {
-                    url: self.ollama_base_url.clone(),
-                    llm_body: llm_body.clone(),
-                },
-                self.logger.clone(),
-            )
-            .await;
+    /// Gets a response from the provider that the settings prefer and falls back to the
+    /// others when it fails
+    ///
+    /// # Arguments
+    ///
+    /// * `llm_body` - The prompt
+    /// * `prompt_key` - The key of the prompt in the response cache
+    /// * `allow_paid` - If OpenAI and Cohere can be used. Only Ollama is used when it's false
+    ///
+    /// # Returns
+    ///
+    /// The provider and model that responded together with the response
+    async fn get_llm_message_based_on_settings(
+        &self,
+        llm_body: LlmBody,
+        prompt_key: u64,
+        allow_paid: bool,
+    ) -> ProviderResponse {
+        let settings = self.settings();
+        let paid_providers = [
+            (
+                Provider::Cohere,
+                &settings.cohere_token,
+                &settings.cohere_model,
+            ),
+            (
+                Provider::OpenAI,
+                &settings.openai_token,
+                &settings.openai_model,
+            ),
+        ];
+
+        // Cohere or OpenAI are only used when their token has been set
+        let mut providers: Vec<(Provider, String)> = paid_providers
+            .into_iter()
+            .filter(|(_, token, _)| allow_paid && !token.is_empty())
+            .map(|(provider, _, model)| (provider, model.clone()))
+            .collect();
+
+        let ollama = (Provider::Ollama, llm_body.model.clone());
+        if settings.priortize_ollama {
+            providers.insert(0, ollama);
+        } else {
+            providers.push(ollama);
+        }
 
-            // If Ollama response is empty and Cohere token is available, fall back to Cohere
-            if ollama_response.message.role.is_empty() && !self.cohere_token.is_empty() {
-                return self.get_cohere_response(llm_body).await;
+        let mut response = None;
+        for (provider, model) in providers {
+            let mut body = llm_body.clone();
+            body.model = model;
+
+            let provider_response = self.get_provider_response(provider, body, prompt_key).await;
+            if !provider_response.response.message.role.is_empty() {
+                return provider_response;
             }
 
-            // If Ollama response has a role and OpenAI token is available, fall back to OpenAI
-            if !ollama_response.message.role.is_empty() && !self.openai_token.is_empty() {
-                return self.get_openai_response(llm_body).await;
+            response = Some(provider_response);
+        }
+
+        // Every provider failed, so return the empty response of the last one
+        response.unwrap_or(ProviderResponse {
+            provider: Provider::Ollama,
+            model: llm_body.model,
+            response: LlmResponse::empty(),
+            cached: false,
+        })
+    }
+
+    /// Gets a response from the cache or otherwise from the provider
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider that should respond
+    /// * `llm_body` - The prompt with the model of the provider
+    /// * `prompt_key` - The key of the prompt in the response cache
+    async fn get_provider_response(
+        &self,
+        provider: Provider,
+        llm_body: LlmBody,
+        prompt_key: u64,
+    ) -> ProviderResponse {
+        let model = llm_body.model.clone();
+        let key = ResponseCache::key(provider, &model, prompt_key);
+
+        if provider == Provider::Ollama && !self.ollama_health.is_available() {
+            self.logger
+                .info("Skipping Ollama, because it's not reachable or doesn't have the model");
+            self.record_request(provider, "skipped");
+            return ProviderResponse {
+                provider,
+                model,
+                response: LlmResponse::empty(),
+                cached: false,
+            };
+        }
+
+        // Before the circuit breaker, which lets only one request through after its cooldown
+        if let Some(response) = self.response_cache.get(key) {
+            self.record_request(provider, "cached");
+            return ProviderResponse {
+                provider,
+                model,
+                response,
+                cached: true,
+            };
+        }
+
+        let circuit_breaker = &self.circuit_breakers[&provider];
+        if !circuit_breaker.allows() {
+            self.logger.info(format!(
+                "Skipping {:?}, because it failed too often",
+                provider
+            ));
+            self.record_request(provider, "skipped");
+            return ProviderResponse {
+                provider,
+                model,
+                response: LlmResponse::empty(),
+                cached: false,
+            };
+        }
+
+        self.logger.info(format!("Using {:?} to respond", provider));
+        let settings = self.settings();
+        let options = settings.request_options[&provider];
+        let started_at = Instant::now();
+        let response = match provider {
+            Provider::Ollama => {
+                Ollama::get_message(
+                    MessageRequest::WithUrl {
+                        url: settings.ollama_base_url.clone(),
+                        num_ctx: settings.ollama_num_ctx,
+                        llm_body,
+                    },
+                    &self.http,
+                    options,
+                    self.logger.clone(),
+                )
+                .await
+            }
+            Provider::OpenAI => {
+                OpenAI::get_message(
+                    MessageRequest::WithToken {
+                        llm_body,
+                        token: settings.openai_token.clone(),
+                    },
+                    &self.http,
+                    options,
+                    self.logger.clone(),
+                )
+                .await
             }
+            Provider::Cohere => {
+                Cohere::get_message(
+                    MessageRequest::WithToken {
+                        llm_body,
+                        token: settings.cohere_token.clone(),
+                    },
+                    &self.http,
+                    options,
+                    self.logger.clone(),
+                )
+                .await
+            }
+        };
 
-            ollama_response
+        let latency = started_at.elapsed();
+        self.metrics
+            .llm_latency
+            .with_label_values(&[provider.as_str()])
+            .observe(latency.as_secs_f64());
+
+        // Empty responses mean something went wrong, so they shouldn't be reused
+        if response.message.role.is_empty() {
+            self.record_request(provider, "failure");
+            circuit_breaker.record_failure();
+            self.provider_stats.record_failure(
+                provider,
+                latency,
+                response.error.as_deref().unwrap_or("Empty response"),
+            );
         } else {
-            // Prioritize Cohere or OpenAI if available, otherwise fall back to Ollama
-            if !self.cohere_token.is_empty() {
-                return self.get_cohere_response(llm_body).await;
-            } else if !self.openai_token.is_empty() {
-                return self.get_openai_response(llm_body).await;
+            self.record_request(provider, "success");
+            if let Some(usage) = response.usage {
+                let tokens = &self.metrics.llm_tokens;
+                tokens
+                    .with_label_values(&[provider.as_str(), "input"])
+                    .inc_by(usage.input_tokens);
+                tokens
+                    .with_label_values(&[provider.as_str(), "output"])
+                    .inc_by(usage.output_tokens);
             }
+            circuit_breaker.record_success();
+            self.provider_stats
+                .record_success(provider, &model, latency);
+            self.response_cache.insert(key, &response);
+        }
 
-            self.logger.info("Using Ollama to respond");
-            Ollama::get_message(
-                MessageRequest::WithUrl {
-                    url: self.ollama_base_url.clone(),
-                    llm_body: llm_body.clone(),
-                },
-                self.logger.clone(),
-            )
-            .await
+        ProviderResponse {
+            provider,
+            model,
+            response,
+            cached: false,
         }
     }
 
-    // Helper function for Cohere
-    async fn get_cohere_response(&self, mut llm_body: LlmBody) -> LlmResponse {
-        self.logger.info("Using Cohere to respond");
-        llm_body.model = self.cohere_model.clone();
-        Cohere::get_message(
-            MessageRequest::WithToken {
-                llm_body,
-                token: self.cohere_token.clone(),
-            },
-            self.logger.clone(),
-        )
-        .await
+    /// Counts an AI request in the metrics
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider the request was for
+    /// * `outcome` - What happened. Like `success` or `cached`
+    fn record_request(&self, provider: Provider, outcome: &str) {
+        self.metrics
+            .llm_requests
+            .with_label_values(&[provider.as_str(), outcome])
+            .inc();
     }
 
-    // Helper function for OpenAI
-    async fn get_openai_response(&self, mut llm_body: LlmBody) -> LlmResponse {
-        self.logger.info("Using OpenAI to respond");
-        llm_body.model = self.openai_model.clone();
-        OpenAI::get_message(
-            MessageRequest::WithToken {
-                llm_body,
-                token: self.openai_token.clone(),
-            },
-            self.logger.clone(),
-        )
-        .await
+    /// This function will collect the values for the system prompt placeholders
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context from where the message is from.
+    /// * `source` - Where the prompt comes from
+    /// * `conversation` - The conversation the message belongs to
+    async fn get_prompt_variables(
+        &self,
+        ctx: &Context,
+        source: &PromptSource<'_>,
+        conversation: &Conversation,
+    ) -> PromptVariables {
+        let bot_name = ctx.cache.current_user().name.clone();
+
+        let guild_name = match source.guild_id {
+            Some(guild_id) => guild_id
+                .name(&ctx.cache)
+                .unwrap_or_else(|| "Unknown server".to_string()),
+            None => "Direct messages".to_string(),
+        };
+
+        let channel_name = source.channel_id.name(ctx).await.unwrap_or_else(|why| {
+            self.logger
+                .debug(format!("Could not get channel name: {}", why).as_str());
+            "unknown".to_string()
+        });
+
+        let participants = conversation
+            .participants
+            .iter()
+            .map(|p| format!("{} (<@{}>)", p.name, p.id))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        PromptVariables {
+            bot_name,
+            guild_name,
+            channel_name,
+            participants,
+        }
+    }
+
+    /// This function will replace placeholders like `{{bot_name}}` inside of the system message
+    ///
+    /// # Arguments
+    ///
+    /// * `system_message` - The system message with placeholders
+    /// * `variables` - The values for the placeholders
+    fn fill_placeholders(&self, system_message: String, variables: &PromptVariables) -> String {
+        let now = chrono::Local::now();
+
+        let placeholders = [
+            ("{{bot_name}}", variables.bot_name.clone()),
+            ("{{guild_name}}", variables.guild_name.clone()),
+            ("{{channel_name}}", variables.channel_name.clone()),
+            ("{{date}}", now.format("%Y-%m-%d").to_string()),
+            ("{{time}}", now.format("%H:%M").to_string()),
+            ("{{participants}}", variables.participants.clone()),
+        ];
+
+        let mut filled = system_message;
+        for (placeholder, value) in placeholders {
+            filled = filled.replace(placeholder, &value);
+        }
+
+        filled
     }
 
     /// This function will format the prompt like: `role: message`
-    fn format_into_prompt(&self, conversation: Conversation) -> Vec<LlmMessage> {
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - The stored conversation with the new prompt
+    /// * `system_message` - The system message with placeholders
+    /// * `variables` - The values for the placeholders
+    fn format_into_prompt(
+        &self,
+        conversation: Conversation,
+        system_message: String,
+        variables: &PromptVariables,
+    ) -> Vec<LlmMessage> {
         let mut messages: Vec<LlmMessage> = Vec::new();
 
         let system_message = LlmMessage {
             role: "system".to_string(),
-            content: self.read_system_message(),
+            content: self.fill_placeholders(system_message, variables),
         };
 
         messages.push(system_message);
@@ -476,34 +888,85 @@ impl AIDolly {
     ///
     /// # Arguments
     ///
-    /// * `msg` - The original Discord message
-    async fn get_llm_message(&self, msg: &Message) -> String {
-        if self.ollama_base_url.is_empty() {
+    /// * `ctx` - The context from where the message is from.
+    /// * `source` - Where the prompt comes from
+    pub async fn get_llm_message(&self, ctx: &Context, source: &PromptSource<'_>) -> String {
+        let settings = self.settings();
+        if settings.ollama_base_url.is_empty() {
             self.logger
                 .error("Ollama url has not been set", Severity::High);
             return "Something went wrong 😭".to_string();
         }
 
+        let allow_paid = match self
+            .usage_tracker
+            .exceeded_budget(source.guild_id, source.author.id)
+        {
+            Some(BudgetAction::Refuse) => {
+                return "The AI budget for today has been used up. Try again tomorrow 💸"
+                    .to_string();
+            }
+            Some(BudgetAction::Ollama) => false,
+            None => true,
+        };
+
         let mut conversation = self.load_conversation();
+        conversation.add_participant(source.author);
+
+        // People that got pinged can also be pinged back
+        let bot_id = ctx.cache.current_user().id;
+        for user in source.mentions.iter().filter(|user| user.id != bot_id) {
+            conversation.add_participant(user);
+        }
+
+        let system_message = self.read_system_message();
+        let prompt_key = ResponseCache::prompt_key(
+            &system_message,
+            &conversation.messages,
+            source.author.id,
+            source.channel_id,
+            source.guild_id,
+            source.content,
+        );
 
         conversation.add_message(
-            format!("{}: {}", msg.author, msg.content),
+            format!("{}: {}", source.author, source.content),
             "user".to_string(),
-            self.max_stored_messages,
+            settings.max_stored_messages,
         );
 
+        let variables = self.get_prompt_variables(ctx, source, &conversation).await;
+
         let prompt_data = LlmBody {
-            model: self.ollama_model.clone(),
-            messages: self.format_into_prompt(conversation.clone()),
+            model: settings.ollama_model.clone(),
+            messages: self.format_into_prompt(conversation.clone(), system_message, &variables),
             stream: false,
         };
 
-        let ollama_response = self.get_llm_message_based_on_settings(prompt_data).await;
+        let ProviderResponse {
+            provider,
+            model,
+            response: ollama_response,
+            cached,
+        } = self
+            .get_llm_message_based_on_settings(prompt_data, prompt_key, allow_paid)
+            .await;
+
+        // Cached responses didn't use any tokens
+        if !cached && !ollama_response.message.role.is_empty() {
+            self.usage_tracker.record(
+                provider,
+                &model,
+                source.guild_id,
+                source.author.id,
+                ollama_response.usage,
+            );
+        }
 
         conversation.add_message(
             ollama_response.message.content.clone(),
             ollama_response.message.role,
-            self.max_stored_messages,
+            settings.max_stored_messages,
         );
         self.save_conversation(conversation);
         let response = self.crop_string(&ollama_response.message.content, 1950);
@@ -512,50 +975,117 @@ impl AIDolly {
         response
     }
 
-    /// This function removes special chars
+    /// Check if message matches any of the triggers
     ///
     /// # Arguments
     ///
-    /// * `input` - The string you want to remove the special chars from.
-    fn remove_special_chars(&self, mut input: String) -> String {
-        let special_chars: Vec<char> = "\\,.!/()@#$%^&*(){{|?'\"<>-+=:;[]}}\n\r ".chars().collect();
-
-        for special_char in special_chars {
-            input = input.replace(special_char, "");
+    /// * `message` - The string of the message you want to check for matches.
+    /// * `triggers` - The triggers that apply to the channel of the message
+    fn contains_names(&self, message: &str, triggers: &TriggerSet) -> bool {
+        match triggers.find_match(message) {
+            Some(trigger) => {
+                self.logger
+                    .debug(format!("Message matched trigger '{}'", trigger.source).as_str());
+                true
+            }
+            None => false,
         }
+    }
+
+    /// The trigger words and respond mode that can be changed while running
+    pub fn response_settings(&self) -> &ResponseSettings {
+        &self.response_settings
+    }
 
-        input
+    /// The guilds, channels and direct messages the AI is allowed to reply in
+    pub fn reply_policy(&self) -> &ReplyPolicy {
+        &self.reply_policy
     }
 
-    /// Check if message contains any string from `responds_to_vec`
+    /// Limits how often the AI can be used per user, channel and in total
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// The providers that have been set up in the environment
+    pub fn configured_providers(&self) -> Vec<Provider> {
+        let settings = self.settings();
+        [
+            (Provider::Ollama, &settings.ollama_base_url),
+            (Provider::Cohere, &settings.cohere_token),
+            (Provider::OpenAI, &settings.openai_token),
+        ]
+        .into_iter()
+        .filter(|(_, setting)| !setting.is_empty())
+        .map(|(provider, _)| provider)
+        .collect()
+    }
+
+    /// The configured providers that can be used right now. Ollama has to be reachable with the
+    /// model and the circuit breaker of the provider can't be open
+    pub fn healthy_providers(&self) -> Vec<Provider> {
+        self.configured_providers()
+            .into_iter()
+            .filter(|provider| {
+                !self.circuit_breakers[provider].is_open()
+                    && (*provider != Provider::Ollama || self.ollama_health.is_available())
+            })
+            .collect()
+    }
+
+    /// The latency, successes and errors per provider
+    pub fn provider_stats(&self) -> &ProviderStats {
+        &self.provider_stats
+    }
+
+    /// The amount of stored messages and participants of the conversation, and the max amount
+    /// of stored messages
+    pub fn conversation_stats(&self) -> (usize, usize, i32) {
+        let conversation = self.load_conversation();
+        (
+            conversation.messages.len(),
+            conversation.participants.len(),
+            self.settings().max_stored_messages,
+        )
+    }
+
+    /// Checks if the Ollama server is reachable and has the model
+    pub fn ollama_health(&self) -> &Arc<OllamaHealth> {
+        &self.ollama_health
+    }
+
+    /// The tokens and cost per provider, guild and user
+    pub fn usage_tracker(&self) -> &UsageTracker {
+        &self.usage_tracker
+    }
+
+    /// Lets the author of the message know they are being rate limited
     ///
     /// # Arguments
     ///
-    /// * `message` - The string of the message you want to check for matches.
-    fn contains_names(&self, message: String) -> bool {
-        for respond in &self.responds_to_vec {
-            let cleaned_message = self.remove_special_chars(message.clone());
-            let cleaned_responds = self.remove_special_chars(respond.clone());
-
-            // Prevent it to respond to everything
-            if cleaned_responds.is_empty() {
-                continue;
-            }
-
-            if respond.starts_with("=") {
-                if cleaned_message.clone() == cleaned_responds.clone() {
-                    return true;
-                } else {
-                    continue;
-                }
-            }
+    /// * `ctx` - The context from where the message is from.
+    /// * `msg` - The message that has been rate limited.
+    /// * `wait_time` - How long they have to wait
+    async fn send_cooldown(&self, ctx: &Context, msg: &Message, wait_time: Duration) {
+        let result = match self.settings().cooldown_reply {
+            CooldownReply::Reaction => msg.react(&ctx.http, '⏳').await.map(|_| ()),
+            CooldownReply::Message => msg
+                .reply(&ctx.http, cooldown_message(wait_time))
+                .await
+                .map(|_| ()),
+            CooldownReply::Silent => Ok(()),
+        };
 
-            if cleaned_message.contains(&cleaned_responds) {
-                return true;
-            }
+        if let Err(why) = result {
+            self.metrics
+                .send_errors
+                .with_label_values(&["message"])
+                .inc();
+            self.logger.error(
+                format!("Could not send cooldown: {why:?}").as_str(),
+                Severity::Low,
+            );
         }
-
-        false
     }
 
     /// Clears the conversation by removing the conversation json file
@@ -593,6 +1123,10 @@ impl AIDolly {
 
 #[async_trait]
 impl MessageHandler for AIDolly {
+    fn name(&self) -> &'static str {
+        "ai_dolly"
+    }
+
     /// This function will respond using a received message from discord using Ollama
     ///
     /// # Arguments
@@ -607,30 +1141,78 @@ impl MessageHandler for AIDolly {
         self.logger
             .debug(format!("The message was formatted: {}", message).as_str());
 
+        let mode = self.response_settings.mode(msg.guild_id, msg.channel_id);
+        let is_mentioned = message.contains(bot_id);
+
         // Respond if responding to all messages is on.
-        // Respond if the message contains the input from the RESPONDS_TO environment
+        // Respond if the message contains one of the triggers of the channel
         // Respond if the bot has been pinged inside of the message
-        if self.respond_to_all_messages
-            || self.contains_names(message.clone())
-            || message.contains(bot_id)
+        let should_respond = match mode {
+            RespondMode::All => true,
+            RespondMode::Mentions => is_mentioned,
+            RespondMode::Triggers => {
+                let triggers = self
+                    .response_settings
+                    .triggers(msg.guild_id, msg.channel_id);
+                is_mentioned || self.contains_names(&msg.content, &triggers)
+            }
+            RespondMode::Off => false,
+        };
+
+        if !should_respond {
+            return false;
+        }
+
+        if !self
+            .reply_policy
+            .allows(ctx, msg.guild_id, msg.channel_id, &msg.author)
+            .await
         {
-            self.logger.info("Using ollama to respond");
-            match msg
-                .channel_id
-                .say(&ctx.http, self.get_llm_message(msg).await)
-                .await
-            {
-                Ok(_) => return true,
-                Err(why) => {
-                    self.logger.error(
-                        format!("Error sending message: {why:?}").as_str(),
-                        Severity::High,
-                    );
-                    return false;
-                }
+            return false;
+        }
+
+        let roles = msg
+            .member
+            .as_ref()
+            .map(|member| member.roles.clone())
+            .unwrap_or_default();
+
+        if let Err(wait_time) =
+            self.rate_limiter
+                .check(ctx.shard_id.0, msg.author.id, msg.channel_id, &roles)
+        {
+            self.metrics
+                .rate_limited
+                .with_label_values(&[&ctx.shard_id.to_string()])
+                .inc();
+            self.send_cooldown(ctx, msg, wait_time).await;
+            // The message has been handled, so the other handlers don't have to
+            return true;
+        }
+
+        self.logger.info("Using ollama to respond");
+        match msg
+            .channel_id
+            .say(
+                &ctx.http,
+                self.get_llm_message(ctx, &PromptSource::from_message(msg))
+                    .await,
+            )
+            .await
+        {
+            Ok(_) => true,
+            Err(why) => {
+                self.metrics
+                    .send_errors
+                    .with_label_values(&["message"])
+                    .inc();
+                self.logger.error(
+                    format!("Error sending message: {why:?}").as_str(),
+                    Severity::High,
+                );
+                false
             }
         }
-        return false;
     }
 
     /// This function will clear the conversations
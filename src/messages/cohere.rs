@@ -1,5 +1,5 @@
-use super::{LlmMessage, LlmProvider, LlmResponse, MessageRequest};
-use rust_logger::{Logger, Severity};
+use super::{LlmMessage, LlmProvider, LlmResponse, LlmUsage, MessageRequest, RequestOptions};
+use crate::components::{HttpClient, Logger, Severity, send_with_retry};
 use serde::Deserialize;
 use serenity::async_trait;
 
@@ -8,6 +8,21 @@ pub struct Cohere;
 #[derive(Debug, Deserialize)]
 struct CohereResponse {
     message: CohereMessage,
+    usage: Option<CohereUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereUsage {
+    tokens: Option<CohereTokens>,
+    billed_units: Option<CohereTokens>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereTokens {
+    #[serde(default)]
+    input_tokens: f64,
+    #[serde(default)]
+    output_tokens: f64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,29 +38,32 @@ struct CohereText {
 
 #[async_trait]
 impl LlmProvider for Cohere {
-    async fn get_message(request: MessageRequest, logger: Logger) -> LlmResponse {
+    async fn get_message(
+        request: MessageRequest,
+        http: &HttpClient,
+        options: RequestOptions,
+        logger: Logger,
+    ) -> LlmResponse {
         match request {
             MessageRequest::WithUrl {
                 llm_body: _,
                 url: _,
-            } => LlmResponse {
-                message: LlmMessage {
-                    content: String::new(),
-                    role: String::new(),
-                },
-            },
+                num_ctx: _,
+            } => LlmResponse::empty(),
             MessageRequest::WithToken { llm_body, token } => {
                 let request_url = "https://api.cohere.com/v2/chat".to_string();
 
                 let request_body = serde_json::to_string(&llm_body).unwrap();
 
-                let response = reqwest::Client::new()
-                    .post(request_url)
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header("Content-Type", "application/json")
-                    .body(request_body)
-                    .send()
-                    .await;
+                let timeout = http.timeout_for(&request_url, options.timeout);
+                let response = send_with_retry(&options.retry, timeout, &logger, || {
+                    http.client()
+                        .post(&request_url)
+                        .header("Authorization", format!("Bearer {}", token))
+                        .header("Content-Type", "application/json")
+                        .body(request_body.clone())
+                })
+                .await;
 
                 match response {
                     Ok(response) => {
@@ -57,19 +75,25 @@ impl LlmProvider for Cohere {
 
                                 if content.is_empty() {
                                     logger.error("Could not get content", Severity::High);
-                                    return LlmResponse {
-                                        message: LlmMessage {
-                                            content: content[0].text.as_str().to_string(),
-                                            role: cohere_response.message.role,
-                                        },
-                                    };
+                                    return LlmResponse::failed(
+                                        "Could not get content".to_string(),
+                                    );
                                 }
 
+                                let usage = cohere_response.usage.and_then(|usage| {
+                                    usage.tokens.or(usage.billed_units).map(|tokens| LlmUsage {
+                                        input_tokens: tokens.input_tokens as u64,
+                                        output_tokens: tokens.output_tokens as u64,
+                                    })
+                                });
+
                                 return LlmResponse {
                                     message: LlmMessage {
                                         content: content[0].text.as_str().to_string(),
                                         role: cohere_response.message.role,
                                     },
+                                    usage,
+                                    error: None,
                                 };
                             }
                             Err(why) => {
@@ -77,21 +101,17 @@ impl LlmProvider for Cohere {
                                     format!("Could not parse Cohere response: {}", why).as_str(),
                                     Severity::High,
                                 );
-                                LlmResponse {
-                                    message: LlmMessage {
-                                        content: String::new(),
-                                        role: String::new(),
-                                    },
-                                }
+                                LlmResponse::failed(why.to_string())
                             }
                         }
                     }
-                    Err(_) => LlmResponse {
-                        message: LlmMessage {
-                            content: String::new(),
-                            role: String::new(),
-                        },
-                    },
+                    Err(why) => {
+                        logger.error(
+                            format!("Cohere request failed: {}", why).as_str(),
+                            Severity::High,
+                        );
+                        LlmResponse::failed(why.to_string())
+                    }
                 }
             }
         }
@@ -1,5 +1,5 @@
 use super::message_handler::MessageHandler;
-use rust_logger::{Logger, Severity};
+use crate::components::{Logger, Severity};
 use serenity::async_trait;
 use serenity::client::Context;
 use serenity::model::channel::Message;
@@ -18,6 +18,10 @@ impl Insult {
 
 #[async_trait]
 impl MessageHandler for Insult {
+    fn name(&self) -> &'static str {
+        "insult"
+    }
+
     async fn respond(&self, ctx: &Context, msg: &Message) -> bool {
         if msg.content == "!ping" {
             match msg.channel_id.say(&ctx.http, "Pong!").await {
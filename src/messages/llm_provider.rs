@@ -1,13 +1,95 @@
 use super::{LlmBody, LlmResponse};
-use rust_logger::Logger;
+use crate::components::{ConfigReader, HttpClient, Logger, RetryPolicy};
 use serenity::async_trait;
+use std::time::Duration;
 
 pub enum MessageRequest {
-    WithUrl { llm_body: LlmBody, url: String },
-    WithToken { llm_body: LlmBody, token: String },
+    WithUrl {
+        llm_body: LlmBody,
+        url: String,
+        num_ctx: u32,
+    },
+    WithToken {
+        llm_body: LlmBody,
+        token: String,
+    },
+}
+
+/// The services that can generate a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Ollama,
+    OpenAI,
+    Cohere,
+}
+
+impl Provider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ollama => "ollama",
+            Self::OpenAI => "openai",
+            Self::Cohere => "cohere",
+        }
+    }
+}
+
+/// The settings of a paid provider
+///
+/// # Fields
+///
+/// * `token` - The API token from `<PROVIDER>_TOKEN`. Empty when the provider isn't used
+/// * `model` - The model from `<PROVIDER>_MODEL`
+/// * `timeout` - The timeout of a single attempt from `<PROVIDER>_TIMEOUT`
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub token: String,
+    pub model: String,
+    pub timeout: Duration,
+}
+
+impl ProviderConfig {
+    /// Reads the settings of a provider from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    /// * `prefix` - The prefix of the settings. Like `OPENAI`
+    /// * `default_model` - The model when `<PROVIDER>_MODEL` has not been set
+    /// * `default_timeout` - The timeout in seconds when `<PROVIDER>_TIMEOUT` has not been set
+    pub fn read(
+        reader: &mut ConfigReader,
+        prefix: &str,
+        default_model: &str,
+        default_timeout: u64,
+    ) -> Self {
+        Self {
+            token: reader.string(&format!("{}_TOKEN", prefix), ""),
+            model: reader.string(&format!("{}_MODEL", prefix), default_model),
+            timeout: Duration::from_secs(
+                reader.number(&format!("{}_TIMEOUT", prefix), default_timeout),
+            ),
+        }
+    }
+}
+
+/// How a provider sends its requests
+///
+/// # Fields
+///
+/// * `timeout` - The timeout of a single attempt
+/// * `retry` - How often and how long to wait before a failed request is tried again
+#[derive(Debug, Clone, Copy)]
+pub struct RequestOptions {
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
 }
 
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
-    async fn get_message(request: MessageRequest, logger: Logger) -> LlmResponse;
+    async fn get_message(
+        request: MessageRequest,
+        http: &HttpClient,
+        options: RequestOptions,
+        logger: Logger,
+    ) -> LlmResponse;
 }
@@ -4,6 +4,8 @@ use serenity::model::channel::Message;
 
 #[async_trait]
 pub trait MessageHandler: Send + Sync {
+    /// The name of the handler, used as label in the metrics
+    fn name(&self) -> &'static str;
     /// This function will respond to the user that send a message
     ///
     /// # Arguments
@@ -4,13 +4,27 @@ pub mod insult;
 pub mod llm_provider;
 pub mod message_handler;
 pub mod ollama;
+pub mod ollama_health;
 pub mod openai;
 pub mod ping;
+pub mod provider_stats;
+pub mod reply_policy;
+pub mod response_cache;
+pub mod response_settings;
+pub mod trigger;
+pub mod usage_tracker;
 pub use ai_dolly::*;
 pub use cohere::*;
 pub use insult::*;
 pub use llm_provider::*;
 pub use message_handler::*;
 pub use ollama::*;
+pub use ollama_health::*;
 pub use openai::*;
 pub use ping::*;
+pub use provider_stats::*;
+pub use reply_policy::*;
+pub use response_cache::*;
+pub use response_settings::*;
+pub use trigger::*;
+pub use usage_tracker::*;
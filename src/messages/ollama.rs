@@ -1,8 +1,8 @@
-use super::{LlmMessage, LlmProvider, LlmResponse, MessageRequest};
-use rust_logger::{Logger, Severity};
-use serde::Serialize;
+use super::{LlmMessage, LlmProvider, LlmResponse, LlmUsage, MessageRequest, RequestOptions};
+use crate::components::{ConfigReader, HttpClient, Logger, Severity, send_with_retry};
+use serde::{Deserialize, Serialize};
 use serenity::async_trait;
-use std::{env, time::Duration};
+use std::time::Duration;
 
 #[derive(Serialize)]
 struct OllamaBody {
@@ -14,37 +14,83 @@ struct OllamaBody {
 
 #[derive(Serialize)]
 struct OllamaOptions {
-    num_ctx: i32,
+    num_ctx: u32,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: LlmMessage,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+/// The settings of the Ollama server
+///
+/// # Fields
+///
+/// * `url` - The url to the server from `OLLAMA_URL`. Empty when it has not been set
+/// * `model` - The model from `OLLAMA_MODEL`. Like `llama3.1`
+/// * `num_ctx` - The context window from `NUM_CTX`
+/// * `timeout` - The timeout of a single attempt from `OLLAMA_TIMEOUT`
+/// * `health_interval` - How often the server is checked from `OLLAMA_HEALTH_INTERVAL`
+/// * `auto_pull` - If a missing model gets pulled from `OLLAMA_AUTO_PULL`
+#[derive(Debug, Clone)]
+pub struct OllamaConfig {
+    pub url: String,
+    pub model: String,
+    pub num_ctx: u32,
+    pub timeout: Duration,
+    pub health_interval: Duration,
+    pub auto_pull: bool,
+}
+
+impl OllamaConfig {
+    /// Reads the settings of the Ollama server from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let url = reader
+            .parse(
+                "OLLAMA_URL",
+                "a url starting with http:// or https://",
+                |value| {
+                    (value.starts_with("http://") || value.starts_with("https://"))
+                        .then(|| value.trim_end_matches('/').to_string())
+                },
+            )
+            .unwrap_or_default();
+
+        Self {
+            url,
+            model: reader.string("OLLAMA_MODEL", "llama3.1"),
+            num_ctx: reader.number("NUM_CTX", 2048),
+            timeout: Duration::from_secs(reader.number("OLLAMA_TIMEOUT", 120)),
+            health_interval: Duration::from_secs(reader.number("OLLAMA_HEALTH_INTERVAL", 60)),
+            auto_pull: reader.bool("OLLAMA_AUTO_PULL", false),
+        }
+    }
 }
 
 pub struct Ollama;
 
 #[async_trait]
 impl LlmProvider for Ollama {
-    async fn get_message(request: MessageRequest, logger: Logger) -> LlmResponse {
+    async fn get_message(
+        request: MessageRequest,
+        http: &HttpClient,
+        options: RequestOptions,
+        logger: Logger,
+    ) -> LlmResponse {
         match request {
-            MessageRequest::WithUrl { llm_body, url } => {
-                // Check if ollama is online by using the / path
-                let ping_url = format!("{}/", url.clone());
-                let ping_client = reqwest::Client::builder()
-                    .timeout(Duration::from_millis(500)) // Applies to the entire request
-                    .build()
-                    .unwrap();
-                let ping_response = ping_client.get(ping_url).send().await;
-                match ping_response {
-                    Ok(_) => {}
-                    Err(_) => {
-                        return LlmResponse {
-                            message: LlmMessage {
-                                content: String::new(),
-                                role: String::new(),
-                            },
-                        };
-                    }
-                }
-
-                let num_ctx: i32 = env::var("NUM_CTX").unwrap().parse().unwrap_or(2048);
-
+            MessageRequest::WithUrl {
+                llm_body,
+                url,
+                num_ctx,
+            } => {
                 logger.debug(format!("Using {} context window", num_ctx).as_str());
 
                 let ollama_body = OllamaBody {
@@ -56,49 +102,47 @@ impl LlmProvider for Ollama {
 
                 let request_url = format!("{}/api/chat", url);
                 let request_body = serde_json::to_string(&ollama_body).unwrap();
-                let response = reqwest::Client::new()
-                    .post(request_url)
-                    .body(request_body)
-                    .send()
-                    .await;
+                let timeout = http.timeout_for(&request_url, options.timeout);
+                let response = send_with_retry(&options.retry, timeout, &logger, || {
+                    http.client().post(&request_url).body(request_body.clone())
+                })
+                .await;
 
                 match response {
                     Ok(response) => {
-                        let response_json = response.json::<LlmResponse>().await;
+                        let response_json = response.json::<OllamaResponse>().await;
 
                         match response_json {
-                            Ok(response) => response,
+                            Ok(response) => LlmResponse {
+                                message: response.message,
+                                usage: Some(LlmUsage {
+                                    input_tokens: response.prompt_eval_count,
+                                    output_tokens: response.eval_count,
+                                }),
+                                error: None,
+                            },
                             Err(why) => {
                                 logger.error(
                                     format!("Could not get Ollama response: {}", why).as_str(),
                                     Severity::High,
                                 );
-                                LlmResponse {
-                                    message: LlmMessage {
-                                        content: String::new(),
-                                        role: String::new(),
-                                    },
-                                }
+                                LlmResponse::failed(why.to_string())
                             }
                         }
                     }
-                    Err(_) => LlmResponse {
-                        message: LlmMessage {
-                            content: String::new(),
-                            role: String::new(),
-                        },
-                    },
+                    Err(why) => {
+                        logger.error(
+                            format!("Ollama request failed: {}", why).as_str(),
+                            Severity::High,
+                        );
+                        LlmResponse::failed(why.to_string())
+                    }
                 }
             }
             MessageRequest::WithToken {
                 llm_body: _,
                 token: _,
-            } => LlmResponse {
-                message: LlmMessage {
-                    content: String::new(),
-                    role: String::new(),
-                },
-            },
+            } => LlmResponse::empty(),
         }
     }
 }
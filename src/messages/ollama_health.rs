@@ -0,0 +1,343 @@
+use super::OllamaConfig;
+use crate::components::{HttpClient, Logger, Severity};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::Notify;
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<TagsModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsModel {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct PullBody {
+    model: String,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    completed: Option<u64>,
+    #[serde(default)]
+    total: Option<u64>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// The result of the last health check
+///
+/// # Fields
+///
+/// * `checked_at` - When the last check was done. `None` before the first check
+/// * `reachable` - If the Ollama server responded
+/// * `model_available` - If `OLLAMA_MODEL` has been pulled on the server
+/// * `models` - The models that are available on the server
+/// * `error` - Why the last check failed
+/// * `pulling` - If the model is being pulled right now
+#[derive(Debug, Clone, Default)]
+pub struct OllamaStatus {
+    pub checked_at: Option<DateTime<Local>>,
+    pub reachable: bool,
+    pub model_available: bool,
+    pub models: Vec<String>,
+    pub error: Option<String>,
+    pub pulling: bool,
+}
+
+/// Checks on startup and every while if the Ollama server is reachable and has the model
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `http` - The shared HTTP client
+/// * `config` - The url, model, interval and auto pull setting, which can be replaced while
+///   running
+/// * `status` - The result of the last check
+/// * `wake` - Starts a check right away after a reload
+pub struct OllamaHealth {
+    logger: Logger,
+    http: Arc<HttpClient>,
+    config: RwLock<Arc<OllamaConfig>>,
+    status: RwLock<OllamaStatus>,
+    wake: Notify,
+}
+
+impl OllamaHealth {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The shared HTTP client
+    /// * `config` - The url, model, interval and auto pull setting from the config
+    pub fn new(http: Arc<HttpClient>, config: &OllamaConfig) -> Self {
+        Self {
+            logger: Logger::new("OllamaHealth"),
+            http,
+            config: RwLock::new(Arc::new(config.clone())),
+            status: RwLock::new(OllamaStatus::default()),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Replaces the url, model, interval and auto pull setting. When the server or model
+    /// changed, the last result is forgotten and the server is checked again right away
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new settings of the Ollama server
+    pub fn reload(&self, config: &OllamaConfig) {
+        let changed = {
+            let mut current = self.config.write().unwrap();
+            let changed = current.url != config.url || current.model != config.model;
+            *current = Arc::new(config.clone());
+            changed
+        };
+
+        if changed {
+            *self.status.write().unwrap() = OllamaStatus::default();
+        }
+
+        self.wake.notify_one();
+    }
+
+    /// Checks the server right away and then every interval in the background. Without a url
+    /// or interval it waits for a reload
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                let config = self.config.read().unwrap().clone();
+
+                if config.url.is_empty() {
+                    self.wake.notified().await;
+                    continue;
+                }
+
+                self.check().await;
+
+                if config.health_interval.is_zero() {
+                    self.wake.notified().await;
+                    continue;
+                }
+
+                select! {
+                    _ = tokio::time::sleep(config.health_interval) => {},
+                    _ = self.wake.notified() => {},
+                }
+            }
+        });
+    }
+
+    /// The result of the last health check
+    pub fn status(&self) -> OllamaStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// The model that should be available
+    pub fn model(&self) -> String {
+        self.config.read().unwrap().model.clone()
+    }
+
+    /// Checks if Ollama can be used. Before the first check it's assumed to work
+    pub fn is_available(&self) -> bool {
+        let status = self.status.read().unwrap();
+        status.checked_at.is_none() || (status.reachable && status.model_available)
+    }
+
+    /// Checks if the model is in the list of models. `llama3.1` matches `llama3.1:latest`
+    fn has_model(model: &str, models: &[String]) -> bool {
+        models.iter().any(|name| {
+            name == model || (!model.contains(':') && name == &format!("{}:latest", model))
+        })
+    }
+
+    /// Asks the server which models it has and pulls the model when it's missing
+    pub async fn check(&self) {
+        // A reload halfway through a check is picked up by the next one
+        let config = self.config.read().unwrap().clone();
+
+        let models = match self.get_models(&config).await {
+            Ok(models) => models,
+            Err(why) => {
+                let was_reachable = {
+                    let mut status = self.status.write().unwrap();
+                    let was_reachable = status.reachable || status.checked_at.is_none();
+                    *status = OllamaStatus {
+                        checked_at: Some(Local::now()),
+                        error: Some(why.clone()),
+                        ..OllamaStatus::default()
+                    };
+                    was_reachable
+                };
+
+                if was_reachable {
+                    self.logger.error(
+                        format!("Ollama is not reachable: {}", why).as_str(),
+                        Severity::High,
+                    );
+                }
+                return;
+            }
+        };
+
+        let mut model_available = Self::has_model(&config.model, &models);
+
+        if !model_available && config.auto_pull {
+            self.status.write().unwrap().pulling = true;
+            model_available = self.pull_model(&config).await;
+        } else if !model_available {
+            self.logger.warning(
+                format!(
+                    "The model '{}' has not been pulled on the Ollama server. Run 'ollama pull {}' or set OLLAMA_AUTO_PULL=true",
+                    config.model, config.model
+                )
+                .as_str(),
+                Severity::High,
+            );
+        }
+
+        let mut status = self.status.write().unwrap();
+        if !status.reachable && status.checked_at.is_some() {
+            self.logger.info("Ollama is reachable again");
+        }
+
+        *status = OllamaStatus {
+            checked_at: Some(Local::now()),
+            reachable: true,
+            model_available,
+            models,
+            error: (!model_available).then(|| format!("Model '{}' is missing", config.model)),
+            pulling: false,
+        };
+    }
+
+    async fn get_models(&self, config: &OllamaConfig) -> Result<Vec<String>, String> {
+        let tags_url = format!("{}/api/tags", config.url);
+
+        let response = self
+            .http
+            .client()
+            .get(&tags_url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|why| why.to_string())?;
+
+        let tags = response
+            .json::<TagsResponse>()
+            .await
+            .map_err(|why| why.to_string())?;
+
+        Ok(tags.models.into_iter().map(|model| model.name).collect())
+    }
+
+    /// Pulls the model and logs the progress. Returns true when it succeeded
+    async fn pull_model(&self, config: &OllamaConfig) -> bool {
+        self.logger
+            .info(format!("Pulling '{}' on the Ollama server", config.model));
+
+        let pull_url = format!("{}/api/pull", config.url);
+        let response = self
+            .http
+            .client()
+            .post(&pull_url)
+            .json(&PullBody {
+                model: config.model.clone(),
+                stream: true,
+            })
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        let mut response = match response {
+            Ok(response) => response,
+            Err(why) => {
+                self.logger.error(
+                    format!("Could not pull '{}': {}", config.model, why).as_str(),
+                    Severity::High,
+                );
+                return false;
+            }
+        };
+
+        // Every line is a json object with the progress
+        let mut buffer = String::new();
+        let mut last_status = String::new();
+        let mut last_percentage = 0;
+        let mut success = false;
+
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(why) => {
+                    self.logger.error(
+                        format!("Pulling '{}' stopped: {}", config.model, why).as_str(),
+                        Severity::High,
+                    );
+                    return false;
+                }
+            };
+
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            while let Some(newline) = buffer.find('\n') {
+                let line: String = buffer.drain(..=newline).collect();
+                let Ok(progress) = serde_json::from_str::<PullProgress>(line.trim()) else {
+                    continue;
+                };
+
+                if let Some(error) = progress.error {
+                    self.logger.error(
+                        format!("Could not pull '{}': {}", config.model, error).as_str(),
+                        Severity::High,
+                    );
+                    return false;
+                }
+
+                if progress.status == "success" {
+                    success = true;
+                }
+
+                if let (Some(completed), Some(total)) = (progress.completed, progress.total)
+                    && total > 0
+                {
+                    // Only log every 10% so the logs stay readable
+                    let percentage = completed * 100 / total;
+                    if progress.status != last_status || percentage >= last_percentage + 10 {
+                        self.logger.info(format!(
+                            "Pulling '{}': {} {}%",
+                            config.model, progress.status, percentage
+                        ));
+                        last_percentage = percentage;
+                    }
+                } else if progress.status != last_status {
+                    self.logger
+                        .info(format!("Pulling '{}': {}", config.model, progress.status));
+                    last_percentage = 0;
+                }
+
+                last_status = progress.status;
+            }
+        }
+
+        if success {
+            self.logger
+                .info(format!("Successfully pulled '{}'", config.model));
+        }
+
+        success
+    }
+}
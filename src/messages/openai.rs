@@ -1,40 +1,50 @@
-use super::{LlmMessage, LlmProvider, LlmResponse, MessageRequest};
-use rust_logger::{Logger, Severity};
+use super::{LlmProvider, LlmResponse, LlmUsage, MessageRequest, RequestOptions};
+use crate::components::{HttpClient, Logger, Severity, send_with_retry};
 use serde::Deserialize;
 use serenity::async_trait;
 
 #[derive(Debug, Clone, Deserialize)]
 struct OpenAIResponse {
     choices: Vec<LlmResponse>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
 }
 
 pub struct OpenAI;
 
 #[async_trait]
 impl LlmProvider for OpenAI {
-    async fn get_message(request: MessageRequest, logger: Logger) -> LlmResponse {
+    async fn get_message(
+        request: MessageRequest,
+        http: &HttpClient,
+        options: RequestOptions,
+        logger: Logger,
+    ) -> LlmResponse {
         match request {
             MessageRequest::WithUrl {
                 llm_body: _,
                 url: _,
-            } => LlmResponse {
-                message: LlmMessage {
-                    content: String::new(),
-                    role: String::new(),
-                },
-            },
+                num_ctx: _,
+            } => LlmResponse::empty(),
             MessageRequest::WithToken { llm_body, token } => {
-                let request_url = "https://api.cohere.com/v2/chat".to_string();
+                let request_url = "https://api.openai.com/v1/chat/completions".to_string();
 
                 let request_body = serde_json::to_string(&llm_body).unwrap();
 
-                let response = reqwest::Client::new()
-                    .post(request_url)
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header("Content-Type", "application/json")
-                    .body(request_body)
-                    .send()
-                    .await;
+                let timeout = http.timeout_for(&request_url, options.timeout);
+                let response = send_with_retry(&options.retry, timeout, &logger, || {
+                    http.client()
+                        .post(&request_url)
+                        .header("Authorization", format!("Bearer {}", token))
+                        .header("Content-Type", "application/json")
+                        .body(request_body.clone())
+                })
+                .await;
 
                 match response {
                     Ok(response) => {
@@ -43,31 +53,35 @@ impl LlmProvider for OpenAI {
                         match openai_response {
                             Ok(response) => {
                                 if response.choices.is_empty() {
-                                    panic!();
+                                    logger.error("OpenAI did not send any choices", Severity::High);
+                                    return LlmResponse::failed(
+                                        "OpenAI did not send any choices".to_string(),
+                                    );
                                 }
 
-                                response.choices[0].clone()
+                                let mut choice = response.choices[0].clone();
+                                choice.usage = response.usage.map(|usage| LlmUsage {
+                                    input_tokens: usage.prompt_tokens,
+                                    output_tokens: usage.completion_tokens,
+                                });
+                                choice
                             }
                             Err(why) => {
                                 logger.error(
                                     format!("Could not get OpenAi message: {}", why).as_str(),
                                     Severity::High,
                                 );
-                                LlmResponse {
-                                    message: LlmMessage {
-                                        content: String::new(),
-                                        role: String::new(),
-                                    },
-                                }
+                                LlmResponse::failed(why.to_string())
                             }
                         }
                     }
-                    Err(_) => LlmResponse {
-                        message: LlmMessage {
-                            content: String::new(),
-                            role: String::new(),
-                        },
-                    },
+                    Err(why) => {
+                        logger.error(
+                            format!("OpenAI request failed: {}", why).as_str(),
+                            Severity::High,
+                        );
+                        LlmResponse::failed(why.to_string())
+                    }
                 }
             }
         }
@@ -0,0 +1,105 @@
+use super::Provider;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// What happened with the requests to a provider
+///
+/// # Fields
+///
+/// * `requests` - The amount of requests that have been sent
+/// * `failures` - The amount of requests that failed
+/// * `total_latency` - How long all requests took together
+/// * `last_success` - When the provider responded the last time
+/// * `last_error` - When and why the provider failed the last time
+#[derive(Debug, Clone, Default)]
+pub struct ProviderStat {
+    pub requests: u64,
+    pub failures: u64,
+    pub total_latency: Duration,
+    pub last_success: Option<DateTime<Local>>,
+    pub last_error: Option<(DateTime<Local>, String)>,
+}
+
+impl ProviderStat {
+    /// How long a request takes on average
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.requests == 0 {
+            return None;
+        }
+
+        Some(self.total_latency / self.requests as u32)
+    }
+}
+
+/// Collects the latency, successes and errors of the providers while the bot is running
+///
+/// # Fields
+///
+/// * `stats` - The stats per provider
+/// * `last_used` - The provider and model that responded the last time
+#[derive(Default)]
+pub struct ProviderStats {
+    stats: Mutex<HashMap<Provider, ProviderStat>>,
+    last_used: Mutex<Option<(Provider, String)>>,
+}
+
+impl ProviderStats {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a request as successful
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider that responded
+    /// * `model` - The model that responded
+    /// * `latency` - How long the request took
+    pub fn record_success(&self, provider: Provider, model: &str, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let stat = stats.entry(provider).or_default();
+        stat.requests += 1;
+        stat.total_latency += latency;
+        stat.last_success = Some(Local::now());
+
+        *self.last_used.lock().unwrap() = Some((provider, model.to_string()));
+    }
+
+    /// Marks a request as failed
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider that failed
+    /// * `latency` - How long the request took
+    /// * `error` - Why the request failed
+    pub fn record_failure(&self, provider: Provider, latency: Duration, error: &str) {
+        let mut stats = self.stats.lock().unwrap();
+        let stat = stats.entry(provider).or_default();
+        stat.requests += 1;
+        stat.failures += 1;
+        stat.total_latency += latency;
+        stat.last_error = Some((Local::now(), error.to_string()));
+    }
+
+    /// The stats of a provider
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider to get the stats of
+    pub fn get(&self, provider: Provider) -> ProviderStat {
+        self.stats
+            .lock()
+            .unwrap()
+            .get(&provider)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The provider and model that responded the last time
+    pub fn last_used(&self) -> Option<(Provider, String)> {
+        self.last_used.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,343 @@
+use crate::components::{ConfigReader, Logger, Severity};
+use serenity::client::Context;
+use serenity::model::channel::{Channel, ChannelType, GuildChannel};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::model::user::User;
+use std::sync::{Arc, RwLock};
+
+/// Who the AI can talk to in direct messages
+///
+/// * `Allow` - Everyone
+/// * `Deny` - No one
+/// * `Members` - Only members of a guild, optionally with one of the roles
+#[derive(Debug, Clone, PartialEq)]
+pub enum DmPolicy {
+    Allow,
+    Deny,
+    Members {
+        guild_id: GuildId,
+        role_ids: Vec<RoleId>,
+    },
+}
+
+impl DmPolicy {
+    /// Parses `allow`, `deny`, `members:<guild_id>` or `members:<guild_id>:<role_id>,<role_id>`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The policy as a string
+    pub fn parse(value: &str) -> Option<Self> {
+        // Ids only have digits, so the whole policy is case-insensitive
+        let value = value.trim().to_lowercase();
+
+        match value.as_str() {
+            "allow" => return Some(Self::Allow),
+            "deny" => return Some(Self::Deny),
+            _ => {}
+        }
+
+        let members = value.strip_prefix("members:")?;
+        let mut split = members.splitn(2, ':');
+        let guild_id = split.next()?.trim().parse::<u64>().ok()?;
+
+        if guild_id == 0 {
+            return None;
+        }
+
+        let mut role_ids = Vec::new();
+        for role_id in split.next().unwrap_or("").split(',') {
+            let role_id = role_id.trim();
+            if role_id.is_empty() {
+                continue;
+            }
+
+            match role_id.parse::<u64>() {
+                Ok(parsed) if parsed != 0 => role_ids.push(RoleId::new(parsed)),
+                _ => return None,
+            }
+        }
+
+        Some(Self::Members {
+            guild_id: GuildId::new(guild_id),
+            role_ids,
+        })
+    }
+}
+
+/// Where the AI is allowed to reply
+///
+/// # Fields
+///
+/// * `enabled_guilds` - The guilds from `ENABLED_GUILDS`
+/// * `disabled_guilds` - The guilds from `DISABLED_GUILDS`
+/// * `allowed_channels` - The channels from `ALLOWED_CHANNELS`
+/// * `denied_channels` - The channels from `DENIED_CHANNELS`
+/// * `allowed_categories` - The categories from `ALLOWED_CATEGORIES`
+/// * `denied_categories` - The categories from `DENIED_CATEGORIES`
+/// * `dm_policy` - The policy from `DM_POLICY`
+#[derive(Debug, Clone)]
+pub struct ReplyPolicyConfig {
+    pub enabled_guilds: Vec<GuildId>,
+    pub disabled_guilds: Vec<GuildId>,
+    pub allowed_channels: Vec<ChannelId>,
+    pub denied_channels: Vec<ChannelId>,
+    pub allowed_categories: Vec<ChannelId>,
+    pub denied_categories: Vec<ChannelId>,
+    pub dm_policy: DmPolicy,
+}
+
+impl ReplyPolicyConfig {
+    /// Reads the lists and the DM policy from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let mut guilds = |key: &str| -> Vec<GuildId> {
+            reader.id_list(key).into_iter().map(GuildId::new).collect()
+        };
+        let enabled_guilds = guilds("ENABLED_GUILDS");
+        let disabled_guilds = guilds("DISABLED_GUILDS");
+
+        let mut channels = |key: &str| -> Vec<ChannelId> {
+            reader
+                .id_list(key)
+                .into_iter()
+                .map(ChannelId::new)
+                .collect()
+        };
+        let allowed_channels = channels("ALLOWED_CHANNELS");
+        let denied_channels = channels("DENIED_CHANNELS");
+        let allowed_categories = channels("ALLOWED_CATEGORIES");
+        let denied_categories = channels("DENIED_CATEGORIES");
+
+        let dm_policy = reader
+            .parse(
+                "DM_POLICY",
+                "allow, deny, members:<guild_id> or members:<guild_id>:<role_id>,<role_id>",
+                DmPolicy::parse,
+            )
+            .unwrap_or(DmPolicy::Allow);
+
+        Self {
+            enabled_guilds,
+            disabled_guilds,
+            allowed_channels,
+            denied_channels,
+            allowed_categories,
+            denied_categories,
+            dm_policy,
+        }
+    }
+}
+
+/// Decides in which guilds, channels and direct messages the AI is allowed to reply
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `config` - The lists and the DM policy, which can be replaced while running.
+///   - `enabled_guilds` - If not empty, the AI only replies in these guilds
+///   - `disabled_guilds` - The AI never replies in these guilds
+///   - `allowed_channels` - If not empty (together with `allowed_categories`), the AI only
+///     replies in these channels
+///   - `denied_channels` - The AI never replies in these channels
+///   - `allowed_categories` - If not empty (together with `allowed_channels`), the AI only
+///     replies in channels of these categories
+///   - `denied_categories` - The AI never replies in channels of these categories
+///   - `dm_policy` - Who the AI can talk to in direct messages
+pub struct ReplyPolicy {
+    logger: Logger,
+    config: RwLock<Arc<ReplyPolicyConfig>>,
+}
+
+impl ReplyPolicy {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The lists and the DM policy from the config
+    pub fn new(config: &ReplyPolicyConfig) -> Self {
+        Self {
+            logger: Logger::new("ReplyPolicy"),
+            config: RwLock::new(Arc::new(config.clone())),
+        }
+    }
+
+    /// Replaces the lists and the DM policy
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new lists and DM policy
+    pub fn reload(&self, config: &ReplyPolicyConfig) {
+        *self.config.write().unwrap() = Arc::new(config.clone());
+    }
+
+    /// Checks if the AI is allowed to reply
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context from where the message is from.
+    /// * `guild_id` - The guild of the message. `None` in direct messages
+    /// * `channel_id` - The channel of the message
+    /// * `author` - The user that wrote the message
+    pub async fn allows(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        author: &User,
+    ) -> bool {
+        // A reload while a channel is being looked up doesn't change the outcome halfway
+        let config = self.config.read().unwrap().clone();
+
+        let allowed = match guild_id {
+            Some(guild_id) => {
+                self.allows_guild_channel(&config, ctx, guild_id, channel_id)
+                    .await
+            }
+            None => self.allows_dm(&config, ctx, author).await,
+        };
+
+        if !allowed {
+            self.logger.debug(
+                format!(
+                    "Not replying in channel {} to {}: blocked by the reply policy",
+                    channel_id, author.id
+                )
+                .as_str(),
+            );
+        }
+
+        allowed
+    }
+
+    async fn allows_dm(&self, config: &ReplyPolicyConfig, ctx: &Context, author: &User) -> bool {
+        match &config.dm_policy {
+            DmPolicy::Allow => true,
+            DmPolicy::Deny => false,
+            DmPolicy::Members { guild_id, role_ids } => {
+                match guild_id.member(ctx, author.id).await {
+                    Ok(member) => {
+                        role_ids.is_empty() || member.roles.iter().any(|r| role_ids.contains(r))
+                    }
+                    Err(why) => {
+                        self.logger.debug(
+                            format!("{} is not a member of {}: {}", author.id, guild_id, why)
+                                .as_str(),
+                        );
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    async fn allows_guild_channel(
+        &self,
+        config: &ReplyPolicyConfig,
+        ctx: &Context,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> bool {
+        if config.disabled_guilds.contains(&guild_id) {
+            return false;
+        }
+
+        if !config.enabled_guilds.is_empty() && !config.enabled_guilds.contains(&guild_id) {
+            return false;
+        }
+
+        let has_channel_rules = !config.allowed_channels.is_empty()
+            || !config.denied_channels.is_empty()
+            || !config.allowed_categories.is_empty()
+            || !config.denied_categories.is_empty();
+
+        if !has_channel_rules {
+            return true;
+        }
+
+        let (channel_ids, category_id) = self.resolve_channel(ctx, channel_id).await;
+
+        if channel_ids
+            .iter()
+            .any(|channel_id| config.denied_channels.contains(channel_id))
+        {
+            return false;
+        }
+
+        if let Some(category_id) = category_id
+            && config.denied_categories.contains(&category_id)
+        {
+            return false;
+        }
+
+        if config.allowed_channels.is_empty() && config.allowed_categories.is_empty() {
+            return true;
+        }
+
+        channel_ids
+            .iter()
+            .any(|channel_id| config.allowed_channels.contains(channel_id))
+            || category_id
+                .is_some_and(|category_id| config.allowed_categories.contains(&category_id))
+    }
+
+    /// Finds the channel (and the parent channel for threads) and the category of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context from where the message is from.
+    /// * `channel_id` - The channel of the message
+    async fn resolve_channel(
+        &self,
+        ctx: &Context,
+        channel_id: ChannelId,
+    ) -> (Vec<ChannelId>, Option<ChannelId>) {
+        let mut channel_ids = vec![channel_id];
+
+        let Some(channel) = self.get_guild_channel(ctx, channel_id).await else {
+            return (channel_ids, None);
+        };
+
+        let is_thread = matches!(
+            channel.kind,
+            ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+        );
+
+        if !is_thread {
+            return (channel_ids, channel.parent_id);
+        }
+
+        // Threads belong to a channel and that channel belongs to the category
+        let Some(parent_id) = channel.parent_id else {
+            return (channel_ids, None);
+        };
+        channel_ids.push(parent_id);
+
+        let category_id = self
+            .get_guild_channel(ctx, parent_id)
+            .await
+            .and_then(|parent| parent.parent_id);
+
+        (channel_ids, category_id)
+    }
+
+    async fn get_guild_channel(
+        &self,
+        ctx: &Context,
+        channel_id: ChannelId,
+    ) -> Option<GuildChannel> {
+        match channel_id.to_channel(ctx).await {
+            Ok(Channel::Guild(channel)) => Some(channel),
+            Ok(_) => None,
+            Err(why) => {
+                self.logger.warning(
+                    format!("Could not get channel {}: {}", channel_id, why).as_str(),
+                    Severity::Low,
+                );
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use super::{LlmMessage, LlmResponse, Provider};
+use crate::components::{ConfigReader, Logger};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// How many messages before the prompt are part of its key. More would make a hit unlikely,
+/// because the conversation grows with every message
+const HISTORY_WINDOW: usize = 2;
+
+/// A response that has been stored in the cache
+struct CachedResponse {
+    response: LlmResponse,
+    stored_at: Instant,
+}
+
+/// How long and how many responses are stored
+///
+/// # Fields
+///
+/// * `ttl` - How long a response can be reused from `RESPONSE_CACHE_TTL`. `None` disables
+///   the cache
+/// * `max_entries` - The max amount of stored responses from `RESPONSE_CACHE_SIZE`
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseCacheConfig {
+    pub ttl: Option<Duration>,
+    pub max_entries: usize,
+}
+
+impl ResponseCacheConfig {
+    /// Reads the TTL and size from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let ttl = reader
+            .parse("RESPONSE_CACHE_TTL", "an amount of seconds", |value| {
+                value.parse::<u64>().ok()
+            })
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+
+        Self {
+            ttl,
+            max_entries: reader.number("RESPONSE_CACHE_SIZE", 100),
+        }
+    }
+}
+
+/// Stores the responses of identical prompts for a while, so common triggers like `=gm` don't
+/// call a provider every time
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `config` - How long a response stays valid and how many can be stored, which can be
+///   replaced while running
+/// * `entries` - The responses per prompt hash
+/// * `hits` - How many times a stored response has been used
+/// * `misses` - How many times no stored response could be used
+pub struct ResponseCache {
+    logger: Logger,
+    config: RwLock<ResponseCacheConfig>,
+    entries: Mutex<HashMap<u64, CachedResponse>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Constructor
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The TTL and size from the config
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        Self {
+            logger: Logger::new("ResponseCache"),
+            config: RwLock::new(*config),
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Replaces the TTL and size and forgets the stored responses, so none of them outlive the
+    /// new TTL
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new TTL and size
+    pub fn reload(&self, config: &ResponseCacheConfig) {
+        *self.config.write().unwrap() = *config;
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Creates the part of the key that is the same for every provider. The system prompt is
+    /// used before its placeholders are filled in, because `{{time}}` changes every minute.
+    /// The other placeholders and the reply depend on who asked where, so the author, channel
+    /// and guild are part of the key
+    ///
+    /// # Arguments
+    ///
+    /// * `system_template` - The system prompt with its placeholders
+    /// * `history` - The conversation before the prompt. Only the last messages are used
+    /// * `author_id` - Who asked it
+    /// * `channel_id` - The channel it was asked in
+    /// * `guild_id` - The guild it was asked in, or `None` for direct messages
+    /// * `prompt` - What the user asked, without who asked it
+    pub fn prompt_key(
+        system_template: &str,
+        history: &[LlmMessage],
+        author_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        prompt: &str,
+    ) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        system_template.hash(&mut hasher);
+        author_id.hash(&mut hasher);
+        channel_id.hash(&mut hasher);
+        guild_id.hash(&mut hasher);
+
+        for message in history.iter().rev().take(HISTORY_WINDOW).rev() {
+            message.role.hash(&mut hasher);
+            message.content.hash(&mut hasher);
+        }
+
+        // `GM`, `gm` and ` gm ` are the same prompt
+        let prompt = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+        prompt.to_lowercase().hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Creates the key of a prompt for a provider and model
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider that gets the prompt
+    /// * `model` - The model that is going to be used
+    /// * `prompt_key` - The key of the prompt from `ResponseCache::prompt_key`
+    pub fn key(provider: Provider, model: &str, prompt_key: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        provider.as_str().hash(&mut hasher);
+        model.hash(&mut hasher);
+        prompt_key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the stored response of a prompt if it's still valid
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the prompt from `ResponseCache::key`
+    pub fn get(&self, key: u64) -> Option<LlmResponse> {
+        let ttl = self.config.read().unwrap().ttl?;
+        let mut entries = self.entries.lock().unwrap();
+
+        let response = match entries.get(&key) {
+            Some(cached) if cached.stored_at.elapsed() < ttl => Some(cached.response.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        let (hits, misses) = if response.is_some() {
+            (
+                self.hits.fetch_add(1, Ordering::Relaxed) + 1,
+                self.misses.load(Ordering::Relaxed),
+            )
+        } else {
+            (
+                self.hits.load(Ordering::Relaxed),
+                self.misses.fetch_add(1, Ordering::Relaxed) + 1,
+            )
+        };
+
+        self.logger.info(format!(
+            "Cache {} ({} hits, {} misses)",
+            if response.is_some() { "hit" } else { "miss" },
+            hits,
+            misses
+        ));
+
+        response
+    }
+
+    /// Stores the response of a prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the prompt from `ResponseCache::key`
+    /// * `response` - The response of the provider
+    pub fn insert(&self, key: u64, response: &LlmResponse) {
+        let config = *self.config.read().unwrap();
+        let Some(ttl) = config.ttl else {
+            return;
+        };
+
+        if config.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= config.max_entries {
+            entries.retain(|_, cached| cached.stored_at.elapsed() < ttl);
+        }
+
+        // Still full, so the oldest response has to make room
+        if entries.len() >= config.max_entries
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.stored_at)
+                .map(|(key, _)| *key)
+        {
+            entries.remove(&oldest);
+        }
+
+        entries.insert(
+            key,
+            CachedResponse {
+                response: response.clone(),
+                stored_at: Instant::now(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> LlmMessage {
+        LlmMessage {
+            content: content.to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    fn response(content: &str) -> LlmResponse {
+        LlmResponse {
+            message: message("assistant", content),
+            usage: None,
+            error: None,
+        }
+    }
+
+    /// The key of a prompt that user 1 asked in channel 2 of guild 3
+    fn prompt_key(system_template: &str, history: &[LlmMessage], prompt: &str) -> u64 {
+        ResponseCache::prompt_key(
+            system_template,
+            history,
+            UserId::new(1),
+            ChannelId::new(2),
+            Some(GuildId::new(3)),
+            prompt,
+        )
+    }
+
+    fn cache() -> ResponseCache {
+        ResponseCache::new(&ResponseCacheConfig {
+            ttl: Some(Duration::from_secs(60)),
+            max_entries: 10,
+        })
+    }
+
+    #[test]
+    fn an_identical_second_prompt_is_a_hit() {
+        let cache = cache();
+        let history = vec![message("user", "<@1>: hi"), message("assistant", "hello")];
+
+        let first = prompt_key("It is {{time}}", &history, "gm");
+        let key = ResponseCache::key(Provider::Ollama, "llama3.1", first);
+        assert!(cache.get(key).is_none());
+        cache.insert(key, &response("good morning"));
+
+        let second = prompt_key("It is {{time}}", &history, "  GM ");
+        let key = ResponseCache::key(Provider::Ollama, "llama3.1", second);
+        assert_eq!(
+            cache.get(key).map(|response| response.message.content),
+            Some("good morning".to_string())
+        );
+    }
+
+    #[test]
+    fn only_the_recent_history_is_part_of_the_key() {
+        let recent = vec![message("user", "<@1>: hi"), message("assistant", "hello")];
+        let mut longer = vec![message("user", "<@2>: something else")];
+        longer.extend(recent.clone());
+
+        assert_eq!(
+            prompt_key("system", &recent, "gm"),
+            prompt_key("system", &longer, "gm")
+        );
+        assert_ne!(
+            prompt_key("system", &recent, "gm"),
+            prompt_key("system", &recent[..1], "gm")
+        );
+    }
+
+    #[test]
+    fn providers_and_models_have_their_own_keys() {
+        let prompt_key = prompt_key("system", &[], "gm");
+
+        assert_ne!(
+            ResponseCache::key(Provider::Ollama, "llama3.1", prompt_key),
+            ResponseCache::key(Provider::OpenAI, "llama3.1", prompt_key)
+        );
+        assert_ne!(
+            ResponseCache::key(Provider::Ollama, "llama3.1", prompt_key),
+            ResponseCache::key(Provider::Ollama, "llama3.2", prompt_key)
+        );
+    }
+
+    #[test]
+    fn every_author_and_channel_has_their_own_keys() {
+        let key = |author: u64, channel: u64, guild: Option<u64>| {
+            ResponseCache::prompt_key(
+                "Hi {{user}} in {{guild}}",
+                &[],
+                UserId::new(author),
+                ChannelId::new(channel),
+                guild.map(GuildId::new),
+                "gm",
+            )
+        };
+
+        assert_eq!(key(1, 2, Some(3)), key(1, 2, Some(3)));
+        assert_ne!(key(1, 2, Some(3)), key(4, 2, Some(3)));
+        assert_ne!(key(1, 2, Some(3)), key(1, 5, Some(3)));
+        assert_ne!(key(1, 2, Some(3)), key(1, 2, None));
+    }
+}
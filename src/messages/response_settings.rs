@@ -0,0 +1,348 @@
+use super::TriggerSet;
+use crate::components::{Logger, Severity, write_atomic};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId};
+use std::collections::HashMap;
+use std::fs::{self, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// When the AI should respond to a message
+///
+/// * `All` - Respond to every message
+/// * `Mentions` - Only respond when the bot gets pinged
+/// * `Triggers` - Respond when the bot gets pinged or a trigger word is used
+/// * `Off` - Never respond
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RespondMode {
+    All,
+    Mentions,
+    Triggers,
+    Off,
+}
+
+impl RespondMode {
+    /// Parses a mode like `all` or `triggers`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The name of the mode
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "mentions" => Some(Self::Mentions),
+            "triggers" => Some(Self::Triggers),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Mentions => "mentions",
+            Self::Triggers => "triggers",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// Where a setting applies to
+#[derive(Debug, Clone, Copy)]
+pub enum SettingsScope {
+    Guild(GuildId),
+    Channel(ChannelId),
+}
+
+impl SettingsScope {
+    /// How the scope is called in the replies of the settings commands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Guild(_) => "this server",
+            Self::Channel(_) => "this channel",
+        }
+    }
+}
+
+/// Settings that override the environment for a guild or channel
+///
+/// # Fields
+///
+/// * `triggers` - The trigger words. `None` means it uses the parent scope
+/// * `mode` - The respond mode. `None` means it uses the parent scope
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScopeSettings {
+    #[serde(default)]
+    pub triggers: Option<Vec<String>>,
+    #[serde(default)]
+    pub mode: Option<RespondMode>,
+}
+
+/// The settings that get stored in the json file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredSettings {
+    #[serde(default)]
+    guilds: HashMap<String, ScopeSettings>,
+    #[serde(default)]
+    channels: HashMap<String, ScopeSettings>,
+}
+
+impl StoredSettings {
+    fn scope(&self, scope: SettingsScope) -> Option<&ScopeSettings> {
+        match scope {
+            SettingsScope::Guild(guild_id) => self.guilds.get(&guild_id.to_string()),
+            SettingsScope::Channel(channel_id) => self.channels.get(&channel_id.to_string()),
+        }
+    }
+
+    fn scope_mut(&mut self, scope: SettingsScope) -> &mut ScopeSettings {
+        match scope {
+            SettingsScope::Guild(guild_id) => self.guilds.entry(guild_id.to_string()).or_default(),
+            SettingsScope::Channel(channel_id) => {
+                self.channels.entry(channel_id.to_string()).or_default()
+            }
+        }
+    }
+}
+
+/// Trigger words and respond modes that can be changed per guild or channel while running.
+/// A channel setting wins from a guild setting and a guild setting wins from the environment.
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `settings_file` - The json file where the settings get stored
+/// * `default_triggers` - The triggers from `RESPONDS_TO`, replaced on a reload
+/// * `default_mode` - The mode based on `RESPOND_TO_ALL_MESSAGES`, replaced on a reload
+/// * `stored` - The overrides per guild and channel
+/// * `compiled` - The compiled triggers per list, so they only get compiled once
+pub struct ResponseSettings {
+    logger: Logger,
+    settings_file: PathBuf,
+    default_triggers: RwLock<Vec<String>>,
+    default_mode: RwLock<RespondMode>,
+    stored: RwLock<StoredSettings>,
+    compiled: RwLock<HashMap<Vec<String>, Arc<TriggerSet>>>,
+}
+
+impl ResponseSettings {
+    /// Constructor that loads the stored settings
+    ///
+    /// # Arguments
+    ///
+    /// * `out_dir` - The directory where the settings are stored
+    /// * `default_triggers` - The triggers from the environment
+    /// * `default_mode` - The respond mode from the environment
+    pub fn new(out_dir: &str, default_triggers: Vec<String>, default_mode: RespondMode) -> Self {
+        let logger = Logger::new("ResponseSettings");
+        let settings_file = Path::new(out_dir).join("response_settings.json");
+
+        let stored = match read_to_string(&settings_file) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|why| {
+                logger.error(
+                    format!("Could not parse '{}': {}", settings_file.display(), why).as_str(),
+                    Severity::Medium,
+                );
+                StoredSettings::default()
+            }),
+            Err(_) => StoredSettings::default(),
+        };
+
+        Self {
+            logger,
+            settings_file,
+            default_triggers: RwLock::new(default_triggers),
+            default_mode: RwLock::new(default_mode),
+            stored: RwLock::new(stored),
+            compiled: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the triggers and respond mode from the environment. The overrides per guild
+    /// and channel are kept
+    ///
+    /// # Arguments
+    ///
+    /// * `default_triggers` - The triggers from the environment
+    /// * `default_mode` - The respond mode from the environment
+    pub fn set_defaults(&self, default_triggers: Vec<String>, default_mode: RespondMode) {
+        *self.default_triggers.write().unwrap() = default_triggers;
+        *self.default_mode.write().unwrap() = default_mode;
+    }
+
+    /// The triggers as they were written that apply to a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `guild_id` - The guild of the channel. `None` in direct messages
+    /// * `channel_id` - The channel of the message
+    pub fn trigger_list(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> Vec<String> {
+        self.triggers_for(&self.stored.read().unwrap(), guild_id, channel_id)
+    }
+
+    /// The compiled triggers that apply to a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `guild_id` - The guild of the channel. `None` in direct messages
+    /// * `channel_id` - The channel of the message
+    pub fn triggers(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> Arc<TriggerSet> {
+        let trigger_list = self.trigger_list(guild_id, channel_id);
+
+        if let Some(compiled) = self.compiled.read().unwrap().get(&trigger_list) {
+            return compiled.clone();
+        }
+
+        let compiled = Arc::new(TriggerSet::compile(&trigger_list, &self.logger));
+        self.compiled
+            .write()
+            .unwrap()
+            .insert(trigger_list, compiled.clone());
+        compiled
+    }
+
+    fn triggers_for(
+        &self,
+        stored: &StoredSettings,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> Vec<String> {
+        let channel = stored.scope(SettingsScope::Channel(channel_id));
+        let guild = guild_id.and_then(|guild_id| stored.scope(SettingsScope::Guild(guild_id)));
+
+        channel
+            .and_then(|settings| settings.triggers.clone())
+            .or_else(|| guild.and_then(|settings| settings.triggers.clone()))
+            .unwrap_or_else(|| self.default_triggers.read().unwrap().clone())
+    }
+
+    /// The respond mode that applies to a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `guild_id` - The guild of the channel. `None` in direct messages
+    /// * `channel_id` - The channel of the message
+    pub fn mode(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> RespondMode {
+        let stored = self.stored.read().unwrap();
+        let channel = stored.scope(SettingsScope::Channel(channel_id));
+        let guild = guild_id.and_then(|guild_id| stored.scope(SettingsScope::Guild(guild_id)));
+
+        channel
+            .and_then(|settings| settings.mode)
+            .or_else(|| guild.and_then(|settings| settings.mode))
+            .unwrap_or(*self.default_mode.read().unwrap())
+    }
+
+    /// Adds a trigger to a guild or channel. Returns false if it was already there
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - Where the trigger should be added
+    /// * `guild_id` - The guild the command was used in, used to inherit its triggers
+    /// * `channel_id` - The channel the command was used in, used to inherit its triggers
+    /// * `trigger` - The trigger to add
+    pub fn add_trigger(
+        &self,
+        scope: SettingsScope,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        trigger: &str,
+    ) -> bool {
+        self.update_triggers(scope, guild_id, channel_id, |triggers| {
+            if triggers.iter().any(|existing| existing == trigger) {
+                return false;
+            }
+
+            triggers.push(trigger.to_string());
+            true
+        })
+    }
+
+    /// Removes a trigger from a guild or channel. Returns false if it wasn't there
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - Where the trigger should be removed
+    /// * `guild_id` - The guild the command was used in, used to inherit its triggers
+    /// * `channel_id` - The channel the command was used in, used to inherit its triggers
+    /// * `trigger` - The trigger to remove
+    pub fn remove_trigger(
+        &self,
+        scope: SettingsScope,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        trigger: &str,
+    ) -> bool {
+        self.update_triggers(scope, guild_id, channel_id, |triggers| {
+            let amount = triggers.len();
+            triggers.retain(|existing| existing != trigger);
+            triggers.len() != amount
+        })
+    }
+
+    fn update_triggers<F: FnOnce(&mut Vec<String>) -> bool>(
+        &self,
+        scope: SettingsScope,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        update: F,
+    ) -> bool {
+        let mut stored = self.stored.write().unwrap();
+
+        // Start from what currently applies, so a new override doesn't lose the inherited triggers
+        let mut triggers = match scope {
+            SettingsScope::Guild(_) => stored
+                .scope(scope)
+                .and_then(|settings| settings.triggers.clone())
+                .unwrap_or_else(|| self.default_triggers.read().unwrap().clone()),
+            SettingsScope::Channel(_) => self.triggers_for(&stored, guild_id, channel_id),
+        };
+
+        if !update(&mut triggers) {
+            return false;
+        }
+
+        stored.scope_mut(scope).triggers = Some(triggers);
+        self.save(&stored);
+
+        // Old lists are not used anymore
+        self.compiled.write().unwrap().clear();
+        true
+    }
+
+    /// Changes the respond mode of a guild or channel
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - Where the mode applies to
+    /// * `mode` - The new respond mode
+    pub fn set_mode(&self, scope: SettingsScope, mode: RespondMode) {
+        let mut stored = self.stored.write().unwrap();
+        stored.scope_mut(scope).mode = Some(mode);
+        self.save(&stored);
+    }
+
+    fn save(&self, stored: &StoredSettings) {
+        if let Some(dir_path) = self.settings_file.parent()
+            && !dir_path.exists()
+            && let Err(e) = fs::create_dir_all(dir_path)
+        {
+            self.logger.error(
+                format!("Failed to create directory: {}", e).as_str(),
+                Severity::High,
+            );
+            return;
+        }
+
+        let json_string = serde_json::to_string_pretty(stored).unwrap();
+
+        match write_atomic(&self.settings_file, &json_string) {
+            Ok(()) => self.logger.info("Saved response settings"),
+            Err(e) => self.logger.error(
+                format!("Could not save response settings: '{}'", e).as_str(),
+                Severity::High,
+            ),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::components::{Logger, Severity};
+use regex::{Regex, RegexBuilder};
+
+/// A compiled trigger that the AI responds to
+///
+/// The grammar of a trigger is:
+///
+/// * `dolly` - Matches `dolly` as a whole word, so `hey dolly!` matches but `idollyzed` doesn't
+/// * `good morning` - Multiple words match with any amount of whitespace in between
+/// * `=gm` - Only matches when the whole message is `gm`, ignoring surrounding punctuation
+/// * `re:^dol+y` - A full regular expression
+/// * `cs:Dolly` - Makes any of the above case-sensitive. Like `cs:=GM` or `cs:re:Dol+y`
+///
+/// # Fields
+///
+/// * `source` - The trigger as it was written
+/// * `regex` - The compiled regular expression
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub source: String,
+    regex: Regex,
+}
+
+impl Trigger {
+    /// Compiles a trigger
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The trigger as it was written. Like `=gm` or `re:dol+y`
+    ///
+    /// # Returns
+    ///
+    /// Why the trigger is invalid as an error. Triggers without a word or pattern, like `=` or
+    /// `cs:re:`, are invalid because they would respond to everything
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (case_sensitive, rest) = match source.strip_prefix("cs:") {
+            Some(rest) => (true, rest),
+            None => (false, source),
+        };
+
+        let (exact, regex, text) = if let Some(pattern) = rest.strip_prefix("re:") {
+            (false, true, pattern)
+        } else if let Some(words) = rest.strip_prefix('=') {
+            (true, false, words)
+        } else {
+            (false, false, rest)
+        };
+
+        if text.trim().is_empty() {
+            return Err("it doesn't have a word or pattern to match".to_string());
+        }
+
+        let pattern = if regex {
+            text.to_string()
+        } else if exact {
+            format!(r"^[\W_]*{}[\W_]*$", Self::words_pattern(text))
+        } else {
+            format!(r"(?:^|[\W_]){}(?:[\W_]|$)", Self::words_pattern(text))
+        };
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|why| why.to_string())?;
+
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+        })
+    }
+
+    /// Escapes the words of a trigger and allows any whitespace in between them
+    fn words_pattern(words: &str) -> String {
+        words
+            .split_whitespace()
+            .map(regex::escape)
+            .collect::<Vec<String>>()
+            .join(r"\s+")
+    }
+
+    /// Checks if the message matches the trigger
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The content of the message
+    pub fn is_match(&self, message: &str) -> bool {
+        self.regex.is_match(message)
+    }
+}
+
+/// A list of compiled triggers
+///
+/// # Fields
+///
+/// * `triggers` - The triggers that compiled successfully
+#[derive(Debug, Clone, Default)]
+pub struct TriggerSet {
+    triggers: Vec<Trigger>,
+}
+
+impl TriggerSet {
+    /// Compiles every trigger and skips the invalid ones with a warning
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The triggers as they were written
+    /// * `logger` - Used for warning about invalid triggers
+    pub fn compile(sources: &[String], logger: &Logger) -> Self {
+        let mut triggers = Vec::new();
+
+        for source in sources.iter().map(|source| source.trim()) {
+            if source.is_empty() {
+                continue;
+            }
+
+            match Trigger::parse(source) {
+                Ok(trigger) => triggers.push(trigger),
+                Err(why) => logger.warning(
+                    format!("Skipping invalid trigger '{}': {}", source, why).as_str(),
+                    Severity::Medium,
+                ),
+            }
+        }
+
+        Self { triggers }
+    }
+
+    /// Returns the first trigger that matches the message
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The content of the message
+    pub fn find_match(&self, message: &str) -> Option<&Trigger> {
+        self.triggers
+            .iter()
+            .find(|trigger| trigger.is_match(message))
+    }
+}
+
+/// Splits a comma separated list of triggers. Use `\,` for a comma inside of a trigger
+///
+/// # Arguments
+///
+/// * `value` - The list like `dolly,=gm,re:a{1\,3}`
+pub fn split_triggers(value: &str) -> Vec<String> {
+    let mut triggers = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                current.push(',');
+                chars.next();
+            }
+            ',' => triggers.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    triggers.push(current);
+
+    triggers
+        .into_iter()
+        .map(|trigger| trigger.trim().to_string())
+        .filter(|trigger| !trigger.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(trigger: &str, message: &str) -> bool {
+        Trigger::parse(trigger).unwrap().is_match(message)
+    }
+
+    #[test]
+    fn words_match_on_word_boundaries() {
+        assert!(matches("dolly", "hey dolly!"));
+        assert!(matches("dolly", "Dolly"));
+        assert!(matches("dolly", "dolly_parton"));
+        assert!(!matches("dolly", "idollyzed"));
+        assert!(!matches("dolly", "dollyparton"));
+    }
+
+    #[test]
+    fn multiple_words_allow_any_whitespace() {
+        assert!(matches("good morning", "good   morning everyone"));
+        assert!(matches("good morning", "GOOD\tMORNING"));
+        assert!(!matches("good morning", "goodmorning"));
+    }
+
+    #[test]
+    fn exact_triggers_only_match_the_whole_message() {
+        assert!(matches("=gm", "gm"));
+        assert!(matches("=gm", "  GM!! "));
+        assert!(!matches("=gm", "gm everyone"));
+        assert!(!matches("=gm", "say gm"));
+    }
+
+    #[test]
+    fn regex_triggers_use_the_pattern() {
+        assert!(matches("re:^dol+y", "dolllly there"));
+        assert!(!matches("re:^dol+y", "hey dolly"));
+        assert!(matches("re:DOLLY", "dolly"));
+    }
+
+    #[test]
+    fn case_sensitive_triggers() {
+        assert!(matches("cs:Dolly", "hey Dolly"));
+        assert!(!matches("cs:Dolly", "hey dolly"));
+        assert!(matches("cs:=GM", "GM!"));
+        assert!(!matches("cs:=GM", "gm"));
+        assert!(matches("cs:re:Dol+y", "Dollly"));
+        assert!(!matches("cs:re:Dol+y", "dollly"));
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert!(matches("c++", "I like c++"));
+        assert!(!matches("a.b", "axb"));
+    }
+
+    #[test]
+    fn rejects_triggers_without_a_word_or_pattern() {
+        for trigger in ["", " ", "=", "= ", "re:", "cs:", "cs:=", "cs:re:"] {
+            assert!(
+                Trigger::parse(trigger).is_err(),
+                "'{}' was accepted",
+                trigger
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        assert!(Trigger::parse("re:(unclosed").is_err());
+    }
+
+    #[test]
+    fn compile_skips_invalid_triggers() {
+        let sources = ["dolly", "=", "re:(", "=gm"].map(String::from);
+        let set = TriggerSet::compile(&sources, &Logger::new("TriggerTest"));
+
+        assert_eq!(set.find_match("hey dolly").unwrap().source, "dolly");
+        assert_eq!(set.find_match("gm").unwrap().source, "=gm");
+        assert!(set.find_match("something else").is_none());
+    }
+
+    #[test]
+    fn split_triggers_supports_escaped_commas() {
+        assert_eq!(
+            split_triggers(r"dolly, =gm ,re:a{1\,3},,  "),
+            vec!["dolly", "=gm", "re:a{1,3}"]
+        );
+    }
+
+    #[test]
+    fn split_triggers_keeps_other_backslashes() {
+        assert_eq!(split_triggers(r"re:\d+,\w"), vec![r"re:\d+", r"\w"]);
+    }
+}
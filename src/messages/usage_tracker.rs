@@ -0,0 +1,370 @@
+use super::{LlmUsage, Provider};
+use crate::components::{ConfigReader, Logger, Severity, write_atomic};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, UserId};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// How many days of usage are kept in the json file
+const KEPT_DAYS: usize = 31;
+
+/// The price of a model in USD per million tokens
+///
+/// # Fields
+///
+/// * `input` - The price per million prompt tokens
+/// * `output` - The price per million response tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    /// Parses a price like `2.5/10`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The input and output price separated by a `/`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (input, output) = value.trim().split_once('/')?;
+        let input = input.trim().parse::<f64>().ok()?;
+        let output = output.trim().parse::<f64>().ok()?;
+
+        if input < 0.0 || output < 0.0 {
+            return None;
+        }
+
+        Some(Self { input, output })
+    }
+
+    fn cost(&self, usage: &LlmUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// What happens when a budget has been used up
+///
+/// * `Ollama` - Only use Ollama, because it doesn't cost anything
+/// * `Refuse` - Don't respond at all
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetAction {
+    Ollama,
+    Refuse,
+}
+
+/// The usage added together
+///
+/// # Fields
+///
+/// * `requests` - The amount of responses
+/// * `input_tokens` - The tokens of all prompts
+/// * `output_tokens` - The tokens of all responses
+/// * `cost` - The estimated cost in USD
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &LlmUsage, cost: f64) {
+        self.requests += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cost += cost;
+    }
+}
+
+/// The usage of a single day
+///
+/// # Fields
+///
+/// * `total` - Everything together
+/// * `providers` - The usage per provider
+/// * `guilds` - The usage per guild id
+/// * `users` - The usage per user id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DayUsage {
+    #[serde(default)]
+    pub total: UsageTotals,
+    #[serde(default)]
+    pub providers: HashMap<String, UsageTotals>,
+    #[serde(default)]
+    pub guilds: HashMap<String, UsageTotals>,
+    #[serde(default)]
+    pub users: HashMap<String, UsageTotals>,
+}
+
+/// The prices and budgets
+///
+/// # Fields
+///
+/// * `prices` - The prices per model from `PRICE_TABLE`
+/// * `daily_budget` - The max cost per day for everyone together from `DAILY_BUDGET`
+/// * `guild_daily_budget` - The max cost per day per guild from `GUILD_DAILY_BUDGET`
+/// * `user_daily_budget` - The max cost per day per user from `USER_DAILY_BUDGET`
+/// * `budget_action` - What happens when a budget is used up from `BUDGET_EXCEEDED_ACTION`
+#[derive(Debug, Clone)]
+pub struct UsageConfig {
+    pub prices: HashMap<String, ModelPrice>,
+    pub daily_budget: Option<f64>,
+    pub guild_daily_budget: Option<f64>,
+    pub user_daily_budget: Option<f64>,
+    pub budget_action: BudgetAction,
+}
+
+impl UsageConfig {
+    /// Reads the prices and budgets from the config
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The merged config
+    pub fn read(reader: &mut ConfigReader) -> Self {
+        let prices = reader
+            .list(
+                "PRICE_TABLE",
+                "model=input/output like gpt-4o=2.5/10",
+                |entry| {
+                    let (model, price) = entry.split_once('=')?;
+                    Some((model.trim().to_string(), ModelPrice::parse(price)?))
+                },
+            )
+            .into_iter()
+            .collect();
+
+        let mut budget = |key: &str| -> Option<f64> {
+            reader.parse(key, "an amount in USD like 2.50", |value| {
+                value.parse::<f64>().ok().filter(|budget| *budget >= 0.0)
+            })
+        };
+        let daily_budget = budget("DAILY_BUDGET");
+        let guild_daily_budget = budget("GUILD_DAILY_BUDGET");
+        let user_daily_budget = budget("USER_DAILY_BUDGET");
+
+        let budget_action = reader
+            .parse(
+                "BUDGET_EXCEEDED_ACTION",
+                "ollama or refuse",
+                |value| match value.to_lowercase().as_str() {
+                    "ollama" => Some(BudgetAction::Ollama),
+                    "refuse" => Some(BudgetAction::Refuse),
+                    _ => None,
+                },
+            )
+            .unwrap_or(BudgetAction::Ollama);
+
+        Self {
+            prices,
+            daily_budget,
+            guild_daily_budget,
+            user_daily_budget,
+            budget_action,
+        }
+    }
+}
+
+/// Keeps track of the tokens and estimated cost per provider, guild, user and day
+///
+/// # Fields
+///
+/// * `logger` - Used for logging information and errors
+/// * `usage_file` - The json file where the usage gets stored
+/// * `config` - The prices and budgets, which can be replaced while running
+/// * `days` - The usage per day like `2024-09-30`
+pub struct UsageTracker {
+    logger: Logger,
+    usage_file: PathBuf,
+    config: RwLock<Arc<UsageConfig>>,
+    days: Mutex<BTreeMap<String, DayUsage>>,
+}
+
+impl UsageTracker {
+    /// Constructor that loads the stored usage
+    ///
+    /// # Arguments
+    ///
+    /// * `out_dir` - The directory where the usage is stored
+    /// * `config` - The prices and budgets from the config
+    pub fn new(out_dir: &str, config: &UsageConfig) -> Self {
+        let logger = Logger::new("UsageTracker");
+        let usage_file = Path::new(out_dir).join("usage.json");
+
+        let days = match read_to_string(&usage_file) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|why| {
+                logger.error(
+                    format!("Could not parse '{}': {}", usage_file.display(), why).as_str(),
+                    Severity::Medium,
+                );
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+
+        Self {
+            logger,
+            usage_file,
+            config: RwLock::new(Arc::new(config.clone())),
+            days: Mutex::new(days),
+        }
+    }
+
+    /// Replaces the prices and budgets. The usage that has been recorded is kept
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The new prices and budgets
+    pub fn reload(&self, config: &UsageConfig) {
+        *self.config.write().unwrap() = Arc::new(config.clone());
+    }
+
+    fn today_key() -> String {
+        chrono::Local::now().format("%Y-%m-%d").to_string()
+    }
+
+    /// Checks if any of the budgets of today has been used up
+    ///
+    /// # Arguments
+    ///
+    /// * `guild_id` - The guild of the prompt. `None` in direct messages
+    /// * `user_id` - The user that wrote the prompt
+    ///
+    /// # Returns
+    ///
+    /// What should happen when a budget has been used up
+    pub fn exceeded_budget(
+        &self,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> Option<BudgetAction> {
+        let config = self.config.read().unwrap().clone();
+        let days = self.days.lock().unwrap();
+        let today = days.get(&Self::today_key())?;
+
+        let over = |totals: Option<&UsageTotals>, budget: Option<f64>| matches!((totals, budget), (Some(totals), Some(budget)) if totals.cost >= budget);
+
+        let guild = guild_id.and_then(|guild_id| today.guilds.get(&guild_id.to_string()));
+        let user = today.users.get(&user_id.to_string());
+
+        if over(Some(&today.total), config.daily_budget)
+            || over(guild, config.guild_daily_budget)
+            || over(user, config.user_daily_budget)
+        {
+            self.logger
+                .info(format!("Budget exceeded for {} in {:?}", user_id, guild_id));
+            return Some(config.budget_action);
+        }
+
+        None
+    }
+
+    /// Adds the usage of a response to today and stores it
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider that responded
+    /// * `model` - The model that responded, used for looking up the price
+    /// * `guild_id` - The guild of the prompt. `None` in direct messages
+    /// * `user_id` - The user that wrote the prompt
+    /// * `usage` - The tokens that were used. Counts as a request without tokens when it's unknown
+    pub fn record(
+        &self,
+        provider: Provider,
+        model: &str,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+        usage: Option<LlmUsage>,
+    ) {
+        let usage = usage.unwrap_or_default();
+        let cost = self
+            .config
+            .read()
+            .unwrap()
+            .prices
+            .get(model)
+            .map(|price| price.cost(&usage))
+            .unwrap_or(0.0);
+
+        self.logger.debug(
+            format!(
+                "{} ({}) used {} input and {} output tokens costing ${:.6}",
+                provider.as_str(),
+                model,
+                usage.input_tokens,
+                usage.output_tokens,
+                cost
+            )
+            .as_str(),
+        );
+
+        let mut days = self.days.lock().unwrap();
+        let today = days.entry(Self::today_key()).or_default();
+
+        today.total.add(&usage, cost);
+        today
+            .providers
+            .entry(provider.as_str().to_string())
+            .or_default()
+            .add(&usage, cost);
+        if let Some(guild_id) = guild_id {
+            today
+                .guilds
+                .entry(guild_id.to_string())
+                .or_default()
+                .add(&usage, cost);
+        }
+        today
+            .users
+            .entry(user_id.to_string())
+            .or_default()
+            .add(&usage, cost);
+
+        // The keys are dates, so the first ones are the oldest
+        while days.len() > KEPT_DAYS {
+            days.pop_first();
+        }
+
+        self.save(&days);
+    }
+
+    /// The usage of today
+    pub fn today(&self) -> DayUsage {
+        self.days
+            .lock()
+            .unwrap()
+            .get(&Self::today_key())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn save(&self, days: &BTreeMap<String, DayUsage>) {
+        if let Some(dir_path) = self.usage_file.parent()
+            && !dir_path.exists()
+            && let Err(e) = fs::create_dir_all(dir_path)
+        {
+            self.logger.error(
+                format!("Failed to create directory: {}", e).as_str(),
+                Severity::High,
+            );
+            return;
+        }
+
+        let json_string = serde_json::to_string_pretty(days).unwrap();
+
+        if let Err(e) = write_atomic(&self.usage_file, &json_string) {
+            self.logger.error(
+                format!("Could not save usage: '{}'", e).as_str(),
+                Severity::High,
+            );
+        }
+    }
+}